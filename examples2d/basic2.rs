@@ -185,6 +185,17 @@ pub fn init_world(testbed: &mut Testbed) {
     }
     */
 
+    /*
+     * Relax the fluids so they start at rest.
+     */
+    let _ = liquid_world.relax_with_coupling(
+        1.0 / 100.0,
+        Some(&Vector2::new(0.0, -9.81)),
+        0.01,
+        50,
+        &mut coupling_set.as_manager_mut(&colliders, &mut bodies),
+    );
+
     /*
      * Set up the testbed.
      */
@@ -7,6 +7,7 @@ use nphysics2d::object::{DefaultBodySet, DefaultColliderSet};
 use nphysics2d::world::{DefaultGeometricalWorld, DefaultMechanicalWorld};
 use nphysics_testbed2d::objects::FluidRenderingMode;
 use nphysics_testbed2d::Testbed;
+use salva2d::counters::Counters;
 use salva2d::coupling::ColliderCouplingSet;
 use salva2d::object::{Boundary, Fluid};
 use salva2d::solver::{DFSPHSolver, NonPressureForce};
@@ -78,6 +79,7 @@ struct CustomForceField {
 impl NonPressureForce<f32> for CustomForceField {
     fn solve(
         &mut self,
+        _counters: &mut Counters,
         _timestep: &salva2d::TimestepManager<f32>,
         _kernel_radius: f32,
         _fluid_fluid_contacts: &salva2d::geometry::ParticlesContacts<f32>,
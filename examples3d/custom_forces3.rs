@@ -7,6 +7,7 @@ use nphysics3d::object::{DefaultBodySet, DefaultColliderSet};
 use nphysics3d::world::{DefaultGeometricalWorld, DefaultMechanicalWorld};
 use nphysics_testbed3d::objects::FluidRenderingMode;
 use nphysics_testbed3d::Testbed;
+use salva3d::counters::Counters;
 use salva3d::coupling::ColliderCouplingSet;
 use salva3d::object::{Boundary, Fluid};
 use salva3d::solver::{DFSPHSolver, NonPressureForce};
@@ -78,6 +79,7 @@ struct CustomForceField {
 impl NonPressureForce<f32> for CustomForceField {
     fn solve(
         &mut self,
+        _counters: &mut Counters,
         _timestep: &salva3d::TimestepManager<f32>,
         _kernel_radius: f32,
         _fluid_fluid_contacts: &salva3d::geometry::ParticlesContacts<f32>,
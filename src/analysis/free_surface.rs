@@ -0,0 +1,266 @@
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+use na::{self, RealField};
+
+use crate::geometry::ContactManager;
+use crate::math::{Vector, DIM};
+use crate::object::{Boundary, Fluid};
+use crate::LiquidWorld;
+
+/// Free-surface detection, with the normals and mean curvature of the surface of the fluids.
+///
+/// All the fluids are treated as a single liquid: the interface between two fluids is not part of the
+/// free surface. Boundary particles are treated as liquid too, so fluid particles touching a boundary
+/// are not detected as surface particles.
+///
+/// The results are stored per fluid, where the `i`-th fluid is the `i`-th fluid of
+/// `LiquidWorld::fluids().as_slice()`. They are computed from the contacts of the last simulation
+/// step, so they must be recomputed after each step, and before adding or removing any particle.
+pub struct FreeSurface<N: RealField> {
+    /// Particles with a color field gradient norm, multiplied by the kernel radius, greater than this
+    /// value are surface particles.
+    pub surface_threshold: N,
+    /// Particles with less neighbors than this are surface particles, whatever their color field gradient.
+    pub min_interior_neighbors: usize,
+    color_gradients: Vec<Vec<Vector<N>>>,
+    normals: Vec<Vec<Vector<N>>>,
+    surface_particles: Vec<Vec<bool>>,
+    curvatures: Vec<Vec<N>>,
+}
+
+impl<N: RealField> FreeSurface<N> {
+    /// Initializes a free-surface detection with default parameters.
+    pub fn new() -> Self {
+        Self {
+            surface_threshold: na::convert(0.5),
+            min_interior_neighbors: if DIM == 2 { 6 } else { 20 },
+            color_gradients: Vec::new(),
+            normals: Vec::new(),
+            surface_particles: Vec::new(),
+            curvatures: Vec::new(),
+        }
+    }
+
+    /// The gradient of the color field at each particle of the `fluid_id`-th fluid.
+    ///
+    /// It points toward the liquid, and is close to zero inside of the liquid.
+    pub fn color_gradients(&self, fluid_id: usize) -> &[Vector<N>] {
+        &self.color_gradients[fluid_id]
+    }
+
+    /// The smoothed outward unit normal at each particle of the `fluid_id`-th fluid.
+    ///
+    /// The normals are only meaningful close to the surface, and are zero where the color field gradient vanishes.
+    pub fn normals(&self, fluid_id: usize) -> &[Vector<N>] {
+        &self.normals[fluid_id]
+    }
+
+    /// Whether each particle of the `fluid_id`-th fluid is at the free surface.
+    pub fn surface_particles(&self, fluid_id: usize) -> &[bool] {
+        &self.surface_particles[fluid_id]
+    }
+
+    /// The mean curvature of the surface at each particle of the `fluid_id`-th fluid.
+    ///
+    /// The curvature is positive where the surface is convex, e.g., for a droplet, and is zero for particles
+    /// that are not at the surface.
+    pub fn curvatures(&self, fluid_id: usize) -> &[N] {
+        &self.curvatures[fluid_id]
+    }
+
+    /// Computes the free surface of the fluids of the given liquid world.
+    pub fn compute_from_world(&mut self, liquid_world: &LiquidWorld<N>) {
+        self.compute(
+            liquid_world.h(),
+            liquid_world.contact_manager(),
+            liquid_world.fluids().as_slice(),
+            liquid_world.boundaries().as_slice(),
+            liquid_world.densities(),
+        )
+    }
+
+    /// Computes the free surface of the given fluids from their contacts and densities.
+    pub fn compute(
+        &mut self,
+        kernel_radius: N,
+        contact_manager: &ContactManager<N>,
+        fluids: &[Fluid<N>],
+        boundaries: &[Boundary<N>],
+        densities: &[Vec<N>],
+    ) {
+        self.color_gradients.resize(fluids.len(), Vec::new());
+        self.normals.resize(fluids.len(), Vec::new());
+        self.surface_particles.resize(fluids.len(), Vec::new());
+        self.curvatures.resize(fluids.len(), Vec::new());
+
+        self.compute_color_gradients(
+            kernel_radius,
+            contact_manager,
+            fluids,
+            boundaries,
+            densities,
+        );
+        self.compute_normals(contact_manager, fluids, densities);
+        self.compute_curvatures(contact_manager, fluids, densities);
+    }
+
+    fn compute_color_gradients(
+        &mut self,
+        kernel_radius: N,
+        contact_manager: &ContactManager<N>,
+        fluids: &[Fluid<N>],
+        boundaries: &[Boundary<N>],
+        densities: &[Vec<N>],
+    ) {
+        let surface_threshold = self.surface_threshold;
+        let min_interior_neighbors = self.min_interior_neighbors;
+
+        for (fluid_id, ((fluid, color_gradients), surface_particles)) in fluids
+            .iter()
+            .zip(self.color_gradients.iter_mut())
+            .zip(self.surface_particles.iter_mut())
+            .enumerate()
+        {
+            color_gradients.resize(fluid.num_particles(), Vector::zeros());
+            surface_particles.resize(fluid.num_particles(), false);
+            let fluid_fluid_contacts = &contact_manager.fluid_fluid_contacts[fluid_id];
+            let fluid_boundary_contacts = &contact_manager.fluid_boundary_contacts[fluid_id];
+
+            par_iter_mut!(color_gradients)
+                .zip(par_iter_mut!(surface_particles))
+                .enumerate()
+                .for_each(|(i, (color_gradient, is_surface))| {
+                    let mut gradient = Vector::zeros();
+                    let mut num_neighbors = 0;
+
+                    for c in fluid_fluid_contacts
+                        .particle_contacts(i)
+                        .read()
+                        .unwrap()
+                        .iter()
+                    {
+                        if !c.is_same_particle_contact() {
+                            num_neighbors += 1;
+                        }
+
+                        gradient += c.gradient
+                            * (fluids[c.j_model].particle_mass(c.j) / densities[c.j_model][c.j]);
+                    }
+
+                    for c in fluid_boundary_contacts
+                        .particle_contacts(i)
+                        .read()
+                        .unwrap()
+                        .iter()
+                    {
+                        num_neighbors += 1;
+                        gradient += c.gradient * boundaries[c.j_model].volumes[c.j];
+                    }
+
+                    *color_gradient = gradient;
+                    *is_surface = num_neighbors < min_interior_neighbors
+                        || gradient.norm() * kernel_radius > surface_threshold;
+                })
+        }
+    }
+
+    fn compute_normals(
+        &mut self,
+        contact_manager: &ContactManager<N>,
+        fluids: &[Fluid<N>],
+        densities: &[Vec<N>],
+    ) {
+        let color_gradients = &self.color_gradients;
+
+        for (fluid_id, (fluid, normals)) in fluids.iter().zip(self.normals.iter_mut()).enumerate() {
+            normals.resize(fluid.num_particles(), Vector::zeros());
+            let fluid_fluid_contacts = &contact_manager.fluid_fluid_contacts[fluid_id];
+
+            par_iter_mut!(normals).enumerate().for_each(|(i, normal)| {
+                let mut smoothed_gradient = Vector::zeros();
+
+                for c in fluid_fluid_contacts
+                    .particle_contacts(i)
+                    .read()
+                    .unwrap()
+                    .iter()
+                {
+                    let volume_j = fluids[c.j_model].particle_mass(c.j) / densities[c.j_model][c.j];
+                    smoothed_gradient += color_gradients[c.j_model][c.j] * (volume_j * c.weight);
+                }
+
+                // NOTE: the color gradient points toward the liquid.
+                *normal = -smoothed_gradient
+                    .try_normalize(N::default_epsilon())
+                    .unwrap_or_else(Vector::zeros);
+            })
+        }
+    }
+
+    fn compute_curvatures(
+        &mut self,
+        contact_manager: &ContactManager<N>,
+        fluids: &[Fluid<N>],
+        densities: &[Vec<N>],
+    ) {
+        let normals = &self.normals;
+        let surface_particles = &self.surface_particles;
+
+        for (fluid_id, (fluid, curvatures)) in
+            fluids.iter().zip(self.curvatures.iter_mut()).enumerate()
+        {
+            curvatures.resize(fluid.num_particles(), N::zero());
+            let fluid_fluid_contacts = &contact_manager.fluid_fluid_contacts[fluid_id];
+
+            par_iter_mut!(curvatures)
+                .enumerate()
+                .for_each(|(i, curvature)| {
+                    *curvature = N::zero();
+
+                    let ni = normals[fluid_id][i];
+
+                    if !surface_particles[fluid_id][i] || ni.norm_squared().is_zero() {
+                        return;
+                    }
+
+                    let mut divergence = N::zero();
+                    let mut normalization = N::zero();
+
+                    for c in fluid_fluid_contacts
+                        .particle_contacts(i)
+                        .read()
+                        .unwrap()
+                        .iter()
+                    {
+                        let nj = normals[c.j_model][c.j];
+
+                        // Only the surface particles have a meaningful normal.
+                        if c.is_same_particle_contact() || !surface_particles[c.j_model][c.j] {
+                            continue;
+                        }
+
+                        let volume_j =
+                            fluids[c.j_model].particle_mass(c.j) / densities[c.j_model][c.j];
+                        let x_ji = fluids[c.j_model].positions[c.j] - fluid.positions[c.i];
+                        let tangential_x_ji = x_ji - ni * ni.dot(&x_ji);
+                        divergence += (nj - ni).dot(&c.gradient) * volume_j;
+                        normalization += tangential_x_ji.dot(&c.gradient) * volume_j;
+                    }
+
+                    // NOTE: the normals only vary along the surface, so the divergence is normalized by the
+                    // tangential moment of the kernel gradient instead of its full moment. This makes the
+                    // result the mean curvature, and compensates for the missing neighbors in the air.
+                    if normalization > N::zero() {
+                        *curvature = divergence / normalization;
+                    }
+                })
+        }
+    }
+}
+
+impl<N: RealField> Default for FreeSurface<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
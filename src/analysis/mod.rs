@@ -0,0 +1,7 @@
+//! Analysis of the state of the fluids, e.g., for rendering or secondary effects.
+
+pub use self::free_surface::FreeSurface;
+pub use self::yu2013_anisotropy::Yu2013Anisotropy;
+
+mod free_surface;
+mod yu2013_anisotropy;
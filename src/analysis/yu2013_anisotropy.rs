@@ -0,0 +1,182 @@
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+use na::{self, RealField};
+
+use crate::geometry::ContactManager;
+use crate::math::{Matrix, Point, Vector, DIM};
+use crate::object::Fluid;
+use crate::LiquidWorld;
+
+// The weighting function used for the weighted PCA.
+fn weight<N: RealField>(distance: N, kernel_radius: N) -> N {
+    (N::one() - (distance / kernel_radius).powi(3)).max(N::zero())
+}
+
+/// Anisotropic particle shapes introduced by Yu and Turk 2013.
+///
+/// A weighted principal component analysis of the neighborhood of each particle yields an ellipsoid
+/// stretched along the directions in which its neighbors are spread, e.g., flattened along thin sheets
+/// of fluid. The centers of the particles are also smoothed with a Laplacian filter to reduce the bumps
+/// caused by the irregular particle distribution. Both can be used to reconstruct smoother surfaces,
+/// e.g., with `SurfaceReconstruction::reconstruct_anisotropic`, or to splat ellipsoids in a renderer.
+///
+/// Only the neighbors belonging to the same fluid are taken into account. The results are stored
+/// per fluid, where the `i`-th fluid is the `i`-th fluid of `LiquidWorld::fluids().as_slice()`. They
+/// are computed from the contacts of the last simulation step, so they must be recomputed after each
+/// step, and before adding or removing any particle.
+pub struct Yu2013Anisotropy<N: RealField> {
+    /// The weight of the Laplacian smoothing of the particle centers, in [0, 1].
+    ///
+    /// The centers are not smoothed if this is zero.
+    pub smoothing_factor: N,
+    /// The maximum ratio between the largest and the smallest axis of an ellipsoid.
+    pub max_anisotropy: N,
+    /// Particles with less neighbors than this are not stretched, but shrunk by `self.isolated_scale`.
+    pub min_neighbors: usize,
+    /// The scaling factor applied to the particles with less neighbors than `self.min_neighbors`.
+    pub isolated_scale: N,
+    centers: Vec<Vec<Point<N>>>,
+    transforms: Vec<Vec<Matrix<N>>>,
+}
+
+impl<N: RealField> Yu2013Anisotropy<N> {
+    /// Initializes an anisotropy computation with default parameters.
+    pub fn new() -> Self {
+        Self {
+            smoothing_factor: na::convert(0.9),
+            max_anisotropy: na::convert(4.0),
+            min_neighbors: if DIM == 2 { 4 } else { 10 },
+            isolated_scale: na::convert(0.5),
+            centers: Vec::new(),
+            transforms: Vec::new(),
+        }
+    }
+
+    /// The smoothed centers of the particles of the `fluid_id`-th fluid.
+    pub fn centers(&self, fluid_id: usize) -> &[Point<N>] {
+        &self.centers[fluid_id]
+    }
+
+    /// The ellipsoid transforms of the particles of the `fluid_id`-th fluid.
+    ///
+    /// Each transform maps the ball of a particle to its ellipsoid, i.e., the ellipsoid of the `i`-th particle
+    /// is the set of points `centers[i] + transforms[i] * v` with `v` in the ball of the particle. The transforms
+    /// of the particles with enough neighbors preserve volumes.
+    pub fn transforms(&self, fluid_id: usize) -> &[Matrix<N>] {
+        &self.transforms[fluid_id]
+    }
+
+    /// Computes the anisotropy of the particles of the fluids of the given liquid world.
+    pub fn compute_from_world(&mut self, liquid_world: &LiquidWorld<N>) {
+        self.compute(
+            liquid_world.h(),
+            liquid_world.contact_manager(),
+            liquid_world.fluids().as_slice(),
+        )
+    }
+
+    /// Computes the anisotropy of the particles of the given fluids from their contacts.
+    pub fn compute(
+        &mut self,
+        kernel_radius: N,
+        contact_manager: &ContactManager<N>,
+        fluids: &[Fluid<N>],
+    ) {
+        let smoothing_factor = self.smoothing_factor;
+        let max_anisotropy = self.max_anisotropy;
+        let min_neighbors = self.min_neighbors;
+        let isolated_scale = self.isolated_scale;
+        let inv_dim: N = na::convert(1.0 / DIM as f64);
+        self.centers.resize(fluids.len(), Vec::new());
+        self.transforms.resize(fluids.len(), Vec::new());
+
+        for (fluid_id, (fluid, (centers, transforms))) in fluids
+            .iter()
+            .zip(self.centers.iter_mut().zip(self.transforms.iter_mut()))
+            .enumerate()
+        {
+            centers.resize(fluid.num_particles(), Point::origin());
+            transforms.resize(fluid.num_particles(), Matrix::identity());
+            let fluid_fluid_contacts = &contact_manager.fluid_fluid_contacts[fluid_id];
+
+            par_iter_mut!(centers)
+                .zip(par_iter_mut!(transforms))
+                .enumerate()
+                .for_each(|(i, (center, transform))| {
+                    let contacts = fluid_fluid_contacts.particle_contacts(i).read().unwrap();
+                    let xi = fluid.positions[i];
+                    let mut total_weight = N::zero();
+                    let mut weighted_mean = Vector::zeros();
+                    let mut num_neighbors = 0;
+
+                    for c in contacts.iter() {
+                        if c.j_model != fluid_id {
+                            continue;
+                        }
+
+                        let x_ji = fluid.positions[c.j] - xi;
+                        let w = weight(x_ji.norm(), kernel_radius);
+                        weighted_mean += x_ji * w;
+                        total_weight += w;
+
+                        if !c.is_same_particle_contact() {
+                            num_neighbors += 1;
+                        }
+                    }
+
+                    // NOTE: the weighted mean is relative to `xi` to limit rounding errors.
+                    if !total_weight.is_zero() {
+                        weighted_mean /= total_weight;
+                    }
+
+                    *center = xi + weighted_mean * smoothing_factor;
+
+                    if num_neighbors < min_neighbors {
+                        *transform = Matrix::identity() * isolated_scale;
+                        return;
+                    }
+
+                    let mut covariance = Matrix::zeros();
+
+                    for c in contacts.iter() {
+                        if c.j_model != fluid_id {
+                            continue;
+                        }
+
+                        let x_ji = fluid.positions[c.j] - xi;
+                        let w = weight(x_ji.norm(), kernel_radius);
+                        let dx = x_ji - weighted_mean;
+                        covariance += dx * dx.transpose() * w;
+                    }
+
+                    covariance /= total_weight;
+
+                    let eigen = covariance.symmetric_eigen();
+                    let max_eigenvalue = eigen.eigenvalues.max();
+
+                    if max_eigenvalue <= N::zero() {
+                        *transform = Matrix::identity() * isolated_scale;
+                        return;
+                    }
+
+                    // The axes of the ellipsoid are proportional to the principal variances, and are
+                    // clamped to prevent extreme stretching.
+                    let min_axis = max_eigenvalue / max_anisotropy;
+                    let axes = eigen.eigenvalues.map(|e| e.max(min_axis));
+                    let volume_scale = axes.iter().fold(N::one(), |acc, a| acc * *a).powf(inv_dim);
+                    let axes = axes / volume_scale;
+
+                    *transform = eigen.eigenvectors
+                        * Matrix::from_diagonal(&axes)
+                        * eigen.eigenvectors.transpose();
+                })
+        }
+    }
+}
+
+impl<N: RealField> Default for Yu2013Anisotropy<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
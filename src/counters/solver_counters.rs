@@ -4,6 +4,10 @@ use std::fmt::{Display, Formatter, Result};
 /// Performance counters related to constraints resolution.
 #[derive(Default, Clone, Copy)]
 pub struct SolverCounters {
+    /// Number of iterations executed by the iterative viscosity solvers.
+    pub viscosity_iterations: usize,
+    /// Number of iterations executed by the iterative elasticity solvers.
+    pub elasticity_iterations: usize,
     /// Time spent for the resolution of non-pressure forces.
     pub non_pressure_resolution_time: Timer,
     /// Time spent for the resolution of pressure forces.
@@ -14,6 +18,8 @@ impl SolverCounters {
     /// Creates a new counter initialized to zero.
     pub fn new() -> Self {
         SolverCounters {
+            viscosity_iterations: 0,
+            elasticity_iterations: 0,
             non_pressure_resolution_time: Timer::new(),
             pressure_resolution_time: Timer::new(),
         }
@@ -33,6 +39,8 @@ impl SolverCounters {
 
     /// Resets to zero all the counters for the solver.
     pub fn reset(&mut self) {
+        self.viscosity_iterations = 0;
+        self.elasticity_iterations = 0;
         self.non_pressure_resolution_time.reset();
         self.pressure_resolution_time.reset();
     }
@@ -40,6 +48,8 @@ impl SolverCounters {
 
 impl Display for SolverCounters {
     fn fmt(&self, f: &mut Formatter) -> Result {
+        writeln!(f, "Viscosity iterations: {}", self.viscosity_iterations)?;
+        writeln!(f, "Elasticity iterations: {}", self.elasticity_iterations)?;
         writeln!(
             f,
             "Non-pressure resolution time: {}",
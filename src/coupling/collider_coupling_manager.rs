@@ -243,7 +243,7 @@ where
         }
     }
 
-    fn transmit_forces(&mut self, boundaries: &BoundarySet<N>) {
+    fn transmit_forces(&mut self, _timestep: &TimestepManager<N>, boundaries: &BoundarySet<N>) {
         for (collider, coupling) in &self.coupling.entries {
             if let (Some(collider), Some(boundary)) = (
                 self.colliders.get(*collider),
@@ -25,7 +25,10 @@ pub trait CouplingManager<N: RealField> {
     );
 
     /// Transmit forces from salva's boundary objects to the coupled bodies.
-    fn transmit_forces(&mut self, boundaries: &BoundarySet<N>);
+    ///
+    /// This is called at the end of each substep, so `timestep.dt()` is the length of
+    /// the substep during which these forces were applied.
+    fn transmit_forces(&mut self, timestep: &TimestepManager<N>, boundaries: &BoundarySet<N>);
 }
 
 impl<N: RealField> CouplingManager<N> for () {
@@ -40,5 +43,5 @@ impl<N: RealField> CouplingManager<N> for () {
     ) {
     }
 
-    fn transmit_forces(&mut self, _: &BoundarySet<N>) {}
+    fn transmit_forces(&mut self, _: &TimestepManager<N>, _: &BoundarySet<N>) {}
 }
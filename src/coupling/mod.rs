@@ -1,11 +1,15 @@
-//! Two-way coupling between nphysics bodies and salva fluids.
+//! Two-way coupling between rigid bodies and salva fluids.
 
 #[cfg(feature = "nphysics")]
 pub use self::collider_coupling_manager::{
     ColliderCouplingManager, ColliderCouplingSet, CouplingMethod,
 };
 pub use self::coupling_manager::CouplingManager;
+pub use self::rigid_body::RigidBody;
+pub use self::rigid_body_set::{RigidBodyHandle, RigidBodySet};
 
 #[cfg(feature = "nphysics")]
 mod collider_coupling_manager;
 mod coupling_manager;
+mod rigid_body;
+mod rigid_body_set;
@@ -0,0 +1,129 @@
+use crate::math::{
+    self, AngularInertia, AngularVector, Isometry, Point, Rotation, Translation, Vector,
+};
+use crate::object::BoundaryHandle;
+use na::{self, RealField};
+
+/// A rigid body simulated by salva itself, and represented by the particles of a boundary object.
+///
+/// The boundary particles are given in the local frame of the rigid body, whose origin must be
+/// the center of mass of the body. At each substep, the world-space positions and velocities of
+/// the boundary particles are updated from the pose and velocities of the body. Then the forces
+/// applied by the fluids on these particles are accumulated to integrate the velocities and the
+/// pose of the body.
+pub struct RigidBody<N: RealField> {
+    /// The pose of the local frame of this rigid body, i.e., the position of its center of mass and its orientation.
+    pub position: Isometry<N>,
+    /// The linear velocity of the center of mass of this rigid body.
+    pub linvel: Vector<N>,
+    /// The angular velocity of this rigid body.
+    pub angvel: AngularVector<N>,
+    /// The mass of this rigid body.
+    ///
+    /// If this is zero, the rigid body is not affected by any force, and moves with constant velocities.
+    pub mass: N,
+    /// The angular inertia of this rigid body, expressed in its local frame.
+    pub angular_inertia: AngularInertia<N>,
+    boundary: BoundaryHandle,
+    local_positions: Vec<Point<N>>,
+    fluid_force: Vector<N>,
+    fluid_torque: AngularVector<N>,
+}
+
+impl<N: RealField> RigidBody<N> {
+    /// Initializes a rigid body at rest.
+    ///
+    /// The boundary object `boundary` must have been added to the liquid world beforehand. Its particles
+    /// will be replaced by `local_positions`, expressed in the local frame of the body, moved by `position`.
+    pub fn new(
+        boundary: BoundaryHandle,
+        local_positions: Vec<Point<N>>,
+        position: Isometry<N>,
+        mass: N,
+        angular_inertia: AngularInertia<N>,
+    ) -> Self {
+        Self {
+            position,
+            linvel: Vector::zeros(),
+            angvel: AngularVector::zeros(),
+            mass,
+            angular_inertia,
+            boundary,
+            local_positions,
+            fluid_force: Vector::zeros(),
+            fluid_torque: AngularVector::zeros(),
+        }
+    }
+
+    /// The handle of the boundary object representing this rigid body.
+    pub fn boundary(&self) -> BoundaryHandle {
+        self.boundary
+    }
+
+    /// The positions of the boundary particles of this rigid body, in its local frame.
+    pub fn local_positions(&self) -> &[Point<N>] {
+        &self.local_positions
+    }
+
+    /// The total force applied by the fluids on this rigid body during the last substep.
+    pub fn fluid_force(&self) -> Vector<N> {
+        self.fluid_force
+    }
+
+    /// The total torque applied by the fluids on this rigid body during the last substep, relative to its center of mass.
+    pub fn fluid_torque(&self) -> AngularVector<N> {
+        self.fluid_torque
+    }
+
+    /// The velocity of the point of this rigid body located at the world-space position `point`.
+    pub fn velocity_at_point(&self, point: &Point<N>) -> Vector<N> {
+        self.linvel
+            + math::angular_cross(
+                &self.angvel,
+                &(point.coords - self.position.translation.vector),
+            )
+    }
+
+    // The inverse of the angular inertia of this rigid body, expressed in world-space.
+    #[cfg(feature = "dim2")]
+    fn world_inv_angular_inertia(&self) -> AngularInertia<N> {
+        self.angular_inertia
+            .try_inverse()
+            .unwrap_or_else(AngularInertia::zeros)
+    }
+
+    // The inverse of the angular inertia of this rigid body, expressed in world-space.
+    #[cfg(feature = "dim3")]
+    fn world_inv_angular_inertia(&self) -> AngularInertia<N> {
+        let rotation = self.position.rotation.to_rotation_matrix();
+        let inv_inertia = self
+            .angular_inertia
+            .try_inverse()
+            .unwrap_or_else(AngularInertia::zeros);
+        rotation.matrix() * inv_inertia * rotation.matrix().transpose()
+    }
+
+    // Accumulates the forces applied by the fluids on the given boundary particles.
+    pub(crate) fn set_fluid_forces(&mut self, positions: &[Point<N>], forces: &[Vector<N>]) {
+        self.fluid_force = Vector::zeros();
+        self.fluid_torque = AngularVector::zeros();
+
+        for (position, force) in positions.iter().zip(forces.iter()) {
+            let arm = position.coords - self.position.translation.vector;
+            self.fluid_force += force;
+            self.fluid_torque += math::gcross_matrix(&arm) * force;
+        }
+    }
+
+    // Integrates the velocities and the pose of this rigid body with a semi-implicit Euler scheme.
+    pub(crate) fn integrate(&mut self, dt: N, gravity: &Vector<N>) {
+        if !self.mass.is_zero() {
+            self.linvel += (self.fluid_force / self.mass + gravity) * dt;
+            self.angvel += self.world_inv_angular_inertia() * self.fluid_torque * dt;
+        }
+
+        let translation = Translation::from(self.position.translation.vector + self.linvel * dt);
+        let rotation = Rotation::from_scaled_axis(self.angvel * dt) * self.position.rotation;
+        self.position = Isometry::from_parts(translation, rotation);
+    }
+}
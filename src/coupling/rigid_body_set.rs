@@ -0,0 +1,252 @@
+use crate::coupling::{CouplingManager, RigidBody};
+use crate::geometry::{HGrid, HGridEntry};
+use crate::math::Vector;
+use crate::object::{BoundarySet, ContiguousArena, ContiguousArenaIndex, Fluid};
+use crate::TimestepManager;
+use na::RealField;
+use std::sync::RwLock;
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+/// The unique identifier of a rigid body.
+pub struct RigidBodyHandle(ContiguousArenaIndex);
+
+impl From<ContiguousArenaIndex> for RigidBodyHandle {
+    #[inline]
+    fn from(i: ContiguousArenaIndex) -> Self {
+        RigidBodyHandle(i)
+    }
+}
+
+impl From<RigidBodyHandle> for ContiguousArenaIndex {
+    #[inline]
+    fn from(handle: RigidBodyHandle) -> Self {
+        handle.0
+    }
+}
+
+/// A set of rigid bodies simulated by salva, and coupled with the fluids through their boundary objects.
+///
+/// This is a lightweight alternative to a full rigid-body physics framework: the rigid bodies only
+/// interact with the fluids, and are subject to gravity. Pass this set as the coupling manager of
+/// `LiquidWorld::step_with_coupling` to simulate them.
+pub struct RigidBodySet<N: RealField> {
+    /// The gravity applied to the rigid bodies.
+    ///
+    /// This should generally be the same as the gravity applied to the fluids.
+    pub gravity: Vector<N>,
+    bodies: ContiguousArena<RigidBodyHandle, RigidBody<N>>,
+}
+
+impl<N: RealField> RigidBodySet<N> {
+    /// Creates an empty set of rigid bodies subject to the given gravity.
+    pub fn new(gravity: Vector<N>) -> Self {
+        Self {
+            gravity,
+            bodies: ContiguousArena::new(),
+        }
+    }
+
+    /// The number of rigid bodies in this set.
+    pub fn len(&self) -> usize {
+        self.bodies.len()
+    }
+
+    /// Whether this set contains no rigid body.
+    pub fn is_empty(&self) -> bool {
+        self.bodies.len() == 0
+    }
+
+    /// Adds a rigid body to this set.
+    pub fn insert(&mut self, body: RigidBody<N>) -> RigidBodyHandle {
+        self.bodies.insert(body)
+    }
+
+    /// Removes a rigid body from this set.
+    ///
+    /// Note that this does not remove its boundary object from the liquid world.
+    pub fn remove(&mut self, handle: RigidBodyHandle) -> Option<RigidBody<N>> {
+        self.bodies.remove(handle)
+    }
+
+    /// Gets the rigid body with the given handle.
+    pub fn get(&self, handle: RigidBodyHandle) -> Option<&RigidBody<N>> {
+        self.bodies.get(handle)
+    }
+
+    /// Gets a mutable reference to the rigid body with the given handle.
+    pub fn get_mut(&mut self, handle: RigidBodyHandle) -> Option<&mut RigidBody<N>> {
+        self.bodies.get_mut(handle)
+    }
+
+    /// Iterates through all the rigid bodies of this set and their handles.
+    pub fn iter<'a>(&'a self) -> impl Iterator<Item = (RigidBodyHandle, &'a RigidBody<N>)> + 'a {
+        self.bodies.iter()
+    }
+}
+
+impl<N: RealField> CouplingManager<N> for RigidBodySet<N> {
+    fn update_boundaries(
+        &mut self,
+        _timestep: &TimestepManager<N>,
+        _h: N,
+        _particle_radius: N,
+        _hgrid: &HGrid<N, HGridEntry>,
+        _fluids: &mut [Fluid<N>],
+        boundaries: &mut BoundarySet<N>,
+    ) {
+        for body in self.bodies.values() {
+            if let Some(boundary) = boundaries.get_mut(body.boundary()) {
+                boundary.positions.clear();
+                boundary.velocities.clear();
+
+                for pt in body.local_positions() {
+                    let pos = body.position * pt;
+                    boundary.velocities.push(body.velocity_at_point(&pos));
+                    boundary.positions.push(pos);
+                }
+
+                boundary
+                    .volumes
+                    .resize(body.local_positions().len(), N::zero());
+
+                if boundary.forces.is_none() {
+                    boundary.forces = Some(RwLock::new(Vec::new()));
+                }
+
+                boundary.clear_forces(true);
+            }
+        }
+    }
+
+    fn transmit_forces(&mut self, timestep: &TimestepManager<N>, boundaries: &BoundarySet<N>) {
+        let gravity = self.gravity;
+        let dt = timestep.dt();
+
+        for body in self.bodies.values_mut() {
+            if let Some(boundary) = boundaries.get(body.boundary()) {
+                if let Some(forces) = &boundary.forces {
+                    body.set_fluid_forces(&boundary.positions, &forces.read().unwrap());
+                }
+            }
+
+            body.integrate(dt, &gravity);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::RigidBodySet;
+    use crate::coupling::RigidBody;
+    use crate::math::{AngularInertia, Isometry, Point, Vector};
+    use crate::object::Boundary;
+    #[cfg(feature = "dim2")]
+    use crate::object::Fluid;
+    use crate::solver::DFSPHSolver;
+    use crate::LiquidWorld;
+
+    #[test]
+    fn free_fall_uses_the_timestep() {
+        let mut world = LiquidWorld::new(DFSPHSolver::<f64>::new(), 0.025, 2.0);
+        let boundary = world.add_boundary(Boundary::new(Vec::new()));
+        let gravity = Vector::y() * -9.81;
+        let mut bodies = RigidBodySet::new(gravity);
+        assert!(bodies.is_empty());
+
+        let handle = bodies.insert(RigidBody::new(
+            boundary,
+            vec![Point::origin()],
+            Isometry::identity(),
+            1.0,
+            AngularInertia::identity(),
+        ));
+        assert_eq!(bodies.len(), 1);
+
+        world.step_with_coupling(0.5, &gravity, &mut bodies);
+        world.step_with_coupling(0.25, &gravity, &mut bodies);
+
+        let body = bodies.get(handle).unwrap();
+        assert_eq!(body.linvel, gravity * 0.75);
+        assert_eq!(
+            body.position.translation.vector,
+            gravity * (0.5 * 0.5 + 0.75 * 0.25)
+        );
+        assert_eq!(
+            world.boundaries().get(boundary).unwrap().positions,
+            vec![Point::from(gravity * 0.25)]
+        );
+    }
+
+    #[cfg(feature = "dim2")]
+    #[test]
+    fn submerged_body_buoyancy() {
+        let particle_radius = 0.025;
+        let spacing = particle_radius * 2.0;
+        let (width, height, body_size) = (10, 10, 4);
+        let mut world = LiquidWorld::new(DFSPHSolver::<f64>::new(), particle_radius, 2.0);
+        let gravity = Vector::y() * -9.81;
+
+        // A container with walls three particles thick.
+        let mut walls = Vec::new();
+
+        for k in 1..4 {
+            for i in -3..width + 3 {
+                walls.push(Point::new(i as f64, -k as f64) * spacing);
+            }
+
+            for j in 0..height + 4 {
+                walls.push(Point::new(-k as f64, j as f64) * spacing);
+                walls.push(Point::new((width - 1 + k) as f64, j as f64) * spacing);
+            }
+        }
+
+        let _ = world.add_boundary(Boundary::new(walls));
+
+        // A square body with an infinite mass, fixed in the middle of the water.
+        let half_size = body_size as f64 * spacing / 2.0;
+        let center = Point::new((width - 1) as f64, height as f64 * 0.8) * spacing / 2.0;
+        let local_positions = (0..body_size * body_size)
+            .map(|id| {
+                Point::new((id % body_size) as f64 + 0.5, (id / body_size) as f64 + 0.5) * spacing
+                    - Vector::repeat(half_size)
+            })
+            .collect();
+        let boundary = world.add_boundary(Boundary::new(Vec::new()));
+        let mut bodies = RigidBodySet::new(gravity);
+        let handle = bodies.insert(RigidBody::new(
+            boundary,
+            local_positions,
+            Isometry::translation(center.x, center.y),
+            0.0,
+            AngularInertia::zeros(),
+        ));
+
+        // The water, with a rest spacing, around the body.
+        let fluid_spacing = spacing * 0.8f64.sqrt();
+        let nx = ((width - 1) as f64 * spacing / fluid_spacing) as usize;
+        let ny = (height as f64 * spacing / fluid_spacing) as usize;
+        let positions = (0..nx * ny)
+            .map(|id| {
+                Point::new((id % nx) as f64, (id / nx) as f64) * fluid_spacing
+                    + Vector::repeat(particle_radius)
+            })
+            .filter(|pt| (pt - center).amax() >= half_size + particle_radius)
+            .collect();
+        let _ = world.add_fluid(Fluid::new(positions, particle_radius, 1000.0));
+
+        // The forces are noisy, so average them once the water is at rest.
+        let mut force = Vector::zeros();
+
+        for i in 0..60 {
+            world.step_with_coupling(1.0 / 60.0, &gravity, &mut bodies);
+
+            if i >= 30 {
+                force += bodies.get(handle).unwrap().fluid_force() / 30.0;
+            }
+        }
+
+        let displaced_weight = 1000.0 * (half_size * 2.0).powi(2) * 9.81;
+        assert!((force.y / displaced_weight - 1.0).abs() < 0.05, "{}", force);
+        assert!(force.x.abs() < displaced_weight * 0.05, "{}", force);
+    }
+}
@@ -127,6 +127,42 @@ impl<N: RealField> ParticlesContacts<N> {
     pub fn apply_permutation(&mut self, _permutation: &[usize]) {
         unimplemented!()
     }
+
+    /// Removes the particles such that `deleted_particles[i]` is `true` from this set of contacts.
+    ///
+    /// All the contacts involving a deleted particle are removed, and the indices of the remaining
+    /// particles are shifted accordingly. This assumes all the contacts are between particles of a single
+    /// fluid, e.g., computed by `compute_self_contacts`.
+    pub fn apply_particles_removal(&mut self, deleted_particles: &[bool]) {
+        let mut new_indices = Vec::with_capacity(deleted_particles.len());
+        let mut num_kept = 0;
+
+        for deleted in deleted_particles {
+            new_indices.push(num_kept);
+
+            if !*deleted {
+                num_kept += 1;
+            }
+        }
+
+        crate::helper::filter_from_mask(deleted_particles, &mut self.contacts);
+
+        for contacts in &mut self.contacts {
+            let contacts = contacts.get_mut().unwrap();
+            contacts.retain(|c| !deleted_particles[c.j]);
+
+            for c in contacts {
+                c.i = new_indices[c.i];
+                c.j = new_indices[c.j];
+            }
+        }
+    }
+
+    // Adds empty contact lists until this set covers `nparticles` particles.
+    pub(crate) fn resize(&mut self, nparticles: usize) {
+        self.contacts
+            .resize_with(nparticles, || RwLock::new(Vec::new()));
+    }
 }
 
 /// Insert all the particles from the given fluids into the `grid`.
@@ -307,6 +343,13 @@ fn compute_contacts_for_pair_of_cells<N: RealField>(
                                 continue;
                             }
 
+                            if !boundaries[*boundary_i]
+                                .interaction_groups
+                                .test(fluids[*fluid_j].interaction_groups)
+                            {
+                                continue;
+                            }
+
                             let pi = &boundaries[*boundary_i].positions[*particle_i];
                             let pj = &fluids[*fluid_j].positions[*particle_j];
 
@@ -332,6 +375,18 @@ fn compute_contacts_for_pair_of_cells<N: RealField>(
             HGridEntry::FluidParticle(fluid_i, particle_i) => {
                 for entry in neighbor_particles {
                     let (fluid_j, particle_j, is_boundary_j) = entry.into_tuple();
+                    let groups_i = fluids[*fluid_i].interaction_groups;
+
+                    if is_boundary_j {
+                        if !groups_i.test(boundaries[fluid_j].interaction_groups) {
+                            continue;
+                        }
+                    } else if *fluid_i != fluid_j
+                        && !groups_i.test(fluids[fluid_j].interaction_groups)
+                    {
+                        continue;
+                    }
+
                     let pi = fluids[*fluid_i].positions[*particle_i];
                     let pj = if is_boundary_j {
                         boundaries[fluid_j].positions[particle_j]
@@ -380,6 +435,15 @@ pub fn compute_self_contacts<N: RealField>(
     h: N,
     fluid: &Fluid<N>,
     contacts: &mut ParticlesContacts<N>,
+) {
+    compute_points_self_contacts(h, &fluid.positions, contacts)
+}
+
+/// Compute all the contacts between the given points, as if they were the particles of a single fluid object.
+pub fn compute_points_self_contacts<N: RealField>(
+    h: N,
+    positions: &[Point<N>],
+    contacts: &mut ParticlesContacts<N>,
 ) {
     contacts
         .contacts
@@ -388,10 +452,10 @@ pub fn compute_self_contacts<N: RealField>(
 
     contacts
         .contacts
-        .resize_with(fluid.num_particles(), || RwLock::new(Vec::new()));
+        .resize_with(positions.len(), || RwLock::new(Vec::new()));
 
     let mut grid = HGrid::new(h);
-    for (i, particle) in fluid.positions.iter().enumerate() {
+    for (i, particle) in positions.iter().enumerate() {
         grid.insert(particle, i);
     }
 
@@ -401,8 +465,8 @@ pub fn compute_self_contacts<N: RealField>(
         for particle_i in curr_particles {
             for (_, nbh_particles) in &neighbors {
                 for particle_j in *nbh_particles {
-                    let pi = fluid.positions[*particle_i];
-                    let pj = fluid.positions[*particle_j];
+                    let pi = positions[*particle_i];
+                    let pj = positions[*particle_j];
 
                     if na::distance_squared(&pi, &pj) <= h * h {
                         let contact = Contact {
@@ -424,3 +488,50 @@ pub fn compute_self_contacts<N: RealField>(
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{compute_points_self_contacts, ParticlesContacts};
+    use crate::math::{Point, Vector};
+
+    // The sorted pairs of particles in contact.
+    fn contact_pairs(contacts: &ParticlesContacts<f64>) -> Vec<(usize, usize)> {
+        let mut result: Vec<_> = contacts
+            .contacts()
+            .iter()
+            .flat_map(|c| {
+                c.read()
+                    .unwrap()
+                    .iter()
+                    .map(|c| (c.i, c.j))
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        result.sort();
+        result
+    }
+
+    #[test]
+    fn particles_removal_remaps_contacts() {
+        let positions: Vec<_> = (0..6)
+            .map(|i| Point::from(Vector::x() * (i as f64 * 0.1)))
+            .collect();
+        let deleted = [false, true, false, false, true, false];
+        let kept: Vec<_> = positions
+            .iter()
+            .zip(deleted.iter())
+            .filter(|(_, deleted)| !**deleted)
+            .map(|(p, _)| *p)
+            .collect();
+
+        let mut contacts = ParticlesContacts::new();
+        compute_points_self_contacts(0.25, &positions, &mut contacts);
+        contacts.apply_particles_removal(&deleted);
+
+        let mut expected = ParticlesContacts::new();
+        compute_points_self_contacts(0.25, &kept, &mut expected);
+
+        assert_eq!(contacts.contacts().len(), kept.len());
+        assert_eq!(contact_pairs(&contacts), contact_pairs(&expected));
+    }
+}
@@ -0,0 +1,58 @@
+/// Pairwise filtering of the interactions between fluids and boundaries.
+///
+/// Each fluid and boundary object belongs to the groups given by the bits set on its
+/// `memberships` mask. Two objects interact only if each of them belongs to at least
+/// one of the groups enabled by the `filter` mask of the other one.
+///
+/// Particles from the same fluid always interact with each other.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct InteractionGroups {
+    /// Groups this object is part of.
+    pub memberships: u32,
+    /// Groups this object can interact with.
+    pub filter: u32,
+}
+
+impl InteractionGroups {
+    /// Initializes interaction groups with the given memberships and filter.
+    pub fn new(memberships: u32, filter: u32) -> Self {
+        Self {
+            memberships,
+            filter,
+        }
+    }
+
+    /// Interaction groups that belong to every group and interact with every group.
+    pub fn all() -> Self {
+        Self::new(u32::MAX, u32::MAX)
+    }
+
+    /// Interaction groups that belong to no group and interact with no group.
+    pub fn none() -> Self {
+        Self::new(0, 0)
+    }
+
+    /// Sets the groups this object is part of.
+    pub fn with_memberships(mut self, memberships: u32) -> Self {
+        self.memberships = memberships;
+        self
+    }
+
+    /// Sets the groups this object can interact with.
+    pub fn with_filter(mut self, filter: u32) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Checks if two objects with the interaction groups `self` and `rhs` can interact.
+    #[inline]
+    pub fn test(self, rhs: Self) -> bool {
+        (self.memberships & rhs.filter) != 0 && (rhs.memberships & self.filter) != 0
+    }
+}
+
+impl Default for InteractionGroups {
+    fn default() -> Self {
+        Self::all()
+    }
+}
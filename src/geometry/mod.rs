@@ -2,11 +2,13 @@
 
 pub use self::contact_manager::ContactManager;
 pub use self::contacts::{
-    compute_contacts, compute_self_contacts, insert_boundaries_to_grid, insert_fluids_to_grid,
-    HGridEntry, ParticlesContacts,
+    compute_contacts, compute_points_self_contacts, compute_self_contacts,
+    insert_boundaries_to_grid, insert_fluids_to_grid, Contact, HGridEntry, ParticlesContacts,
 };
 pub use self::hgrid::HGrid;
+pub use self::interaction_groups::InteractionGroups;
 
 mod contact_manager;
 mod contacts;
 mod hgrid;
+mod interaction_groups;
@@ -16,11 +16,15 @@ The name of this library is inspired from the famous surrealist artist `Salvador
 
 ## Features
 - **Pressure resolution:** DFSPH and IISPH.
-- **Viscosity:** DFSPH viscosity, Artificial viscosity, and XSPH viscosity.
-- **Surface tension:** WCSPH surface tension, and methods from He et al. 2014 and Akinci et al. 2013
-- **Elasticity:** method from Becker et al. 2009
-- **Multiphase fluids**: mix several fluids with different characteristics (densities, viscosities, etc.)
-- Optional **two-way coupling** with bodies from **nphysics**.
+- **Viscosity:** DFSPH viscosity, Artificial viscosity, XSPH viscosity, and implicit viscosity from Weiler et al. 2018.
+- **Surface tension:** WCSPH surface tension, and methods from He et al. 2014 and Akinci et al. 2013, as well as interfacial tension between fluids from Hu and Adams 2006.
+- **Elasticity:** method from Becker et al. 2009, with plastic deformations and fractures, implicit elasticity from Peer et al. 2018, and contacts between elastic bodies.
+- **Granular materials:** sand and soils with a Drucker-Prager yield criterion, from the elastic-plastic model of Bui et al. 2008.
+- **Multiphase fluids**: mix several fluids with different characteristics (densities, viscosities, etc.), including miscible fluids from Ren et al. 2014.
+- **Diffuse particles:** spray, foam, and bubbles for whitewater effects from Ihmsen et al. 2012.
+- **Surface analysis:** free-surface particle detection with smoothed normals and mean curvature.
+- **Surface reconstruction:** watertight meshes from marching cubes (or marching squares in 2D), with OBJ and PLY export, anisotropic kernels from Yu and Turk 2013, and narrow-band signed distance fields from Zhu and Bridson 2005.
+- **Two-way coupling** with built-in particle-based rigid bodies, or optionally with bodies from **nphysics**.
 - **WASM** support
 */
 #![deny(non_camel_case_types)]
@@ -77,6 +81,7 @@ macro_rules! par_reduce_sum {
     }};
 }
 
+pub mod analysis;
 pub mod counters;
 pub mod coupling;
 pub mod geometry;
@@ -84,6 +89,7 @@ pub mod helper;
 pub mod kernel;
 mod liquid_world;
 pub mod object;
+pub mod reconstruction;
 #[cfg(feature = "sampling")]
 pub mod sampling;
 pub mod solver;
@@ -169,6 +175,11 @@ pub mod math {
     pub fn gcross_matrix<N: RealField>(v: &Vector<N>) -> Matrix<N> {
         v.cross_matrix()
     }
+
+    /// The velocity induced by the angular velocity `w` at the relative position `v`.
+    pub fn angular_cross<N: RealField>(w: &AngularVector<N>, v: &Vector<N>) -> Vector<N> {
+        w.cross(v)
+    }
 }
 
 /// Compilation flags dependent aliases for mathematical types.
@@ -248,4 +259,9 @@ pub mod math {
     pub fn gcross_matrix<N: RealField>(v: &Vector<N>) -> RowVector2<N> {
         RowVector2::new(-v.y, v.x)
     }
+
+    /// The velocity induced by the angular velocity `w` at the relative position `v`.
+    pub fn angular_cross<N: RealField>(w: &AngularVector<N>, v: &Vector<N>) -> Vector<N> {
+        Vector2::new(-w.x * v.y, w.x * v.x)
+    }
 }
@@ -2,9 +2,10 @@ use crate::counters::Counters;
 use crate::coupling::CouplingManager;
 use crate::geometry::{self, ContactManager, HGrid, HGridEntry};
 use crate::math::Vector;
-use crate::object::{Boundary, BoundaryHandle, BoundarySet};
+use crate::object::{Boundary, BoundaryHandle, BoundarySet, DiffuseParticles};
 use crate::object::{Fluid, FluidHandle, FluidSet};
-use crate::solver::PressureSolver;
+use crate::solver::heat_transfer::HeatTransfer;
+use crate::solver::{Ihmsen2012DiffuseParticles, MultiphaseForce, PressureSolver};
 use crate::TimestepManager;
 use na::RealField;
 
@@ -13,11 +14,15 @@ pub struct LiquidWorld<N: RealField> {
     /// Performance counters of the whole fluid simulation engine.
     pub counters: Counters,
     nsubsteps_since_sort: usize,
+    time: N,
     particle_radius: N,
     h: N,
     fluids: FluidSet<N>,
     boundaries: BoundarySet<N>,
     solver: Box<dyn PressureSolver<N>>,
+    multiphase_forces: Vec<Box<dyn MultiphaseForce<N>>>,
+    diffuse_particles: Option<Ihmsen2012DiffuseParticles<N>>,
+    heat_transfer: HeatTransfer<N>,
     contact_manager: ContactManager<N>,
     timestep_manager: TimestepManager<N>,
     hgrid: HGrid<N, HGridEntry>,
@@ -40,11 +45,15 @@ impl<N: RealField> LiquidWorld<N> {
         Self {
             counters: Counters::new(),
             nsubsteps_since_sort: 0,
+            time: N::zero(),
             particle_radius,
             h,
             fluids: FluidSet::new(),
             boundaries: BoundarySet::new(),
             solver: Box::new(solver),
+            multiphase_forces: Vec::new(),
+            diffuse_particles: None,
+            heat_transfer: HeatTransfer::new(),
             contact_manager: ContactManager::new(),
             timestep_manager: TimestepManager::new(particle_radius),
             hgrid: HGrid::new(h),
@@ -75,81 +84,293 @@ impl<N: RealField> LiquidWorld<N> {
             fluid.apply_particles_removal();
         }
 
+        for boundary in self.boundaries.as_mut_slice() {
+            boundary.reset_force_measurement();
+        }
+
         // Perform substeps.
         while !self.timestep_manager.is_done() {
-            self.nsubsteps_since_sort += 1;
-            self.counters.nsubsteps += 1;
+            self.substep(gravity, coupling, false);
+        }
 
-            self.counters.stages.collision_detection_time.resume();
-            self.counters.cd.grid_insertion_time.resume();
-            self.hgrid.clear();
-            geometry::insert_fluids_to_grid(self.fluids.as_slice(), &mut self.hgrid);
-            self.counters.cd.grid_insertion_time.pause();
+        self.time += dt;
 
-            self.counters.cd.boundary_update_time.resume();
-            coupling.update_boundaries(
-                &self.timestep_manager,
-                self.h,
-                self.particle_radius,
-                &self.hgrid,
-                self.fluids.as_mut_slice(),
-                &mut self.boundaries,
-            );
-            self.counters.cd.boundary_update_time.pause();
+        //        if self.nsubsteps_since_sort >= 100 {
+        //            self.nsubsteps_since_sort = 0;
+        //            println!("Performing z-sort of particles.");
+        //            par_iter_mut!(self.fluids.as_mut_slice()).for_each(|fluid| fluid.z_sort())
+        //        }
+
+        self.counters.step_time.pause();
+        //        println!("Counters: {}", self.counters);
+    }
+
+    /// Relaxes the fluids of this world so they start at rest.
+    ///
+    /// A fluid initialized from a block of grid points is generally out of equilibrium: it will compress
+    /// under gravity and bounce before settling. This iterates the pressure solver with timesteps of
+    /// length `dt`, zeroing the fluid velocities after each iteration, until the average density error of
+    /// every fluid drops below `max_density_error` (e.g. `0.01` for 1%), or until `max_iter` iterations have
+    /// been executed.
+    ///
+    /// If `gravity` is not `None`, the fluids are relaxed toward their hydrostatic state under this
+    /// gravity. Otherwise they are only relaxed toward their rest density.
+    ///
+    /// Only the particle positions are meant to change: the simulation time does not advance, the kinematic
+    /// boundaries stay still at their pose at the current time, and neither the multiphase forces, the heat
+    /// transfer, nor the diffuse particles are updated. However, the state of the non-pressure forces attached
+    /// to the fluids still evolves at each iteration, e.g., the plastic strains and phases of the elasticity
+    /// models, the angular velocities of micropolar fluids, and the stresses of granular materials.
+    ///
+    /// Returns the number of iterations executed.
+    pub fn relax(
+        &mut self,
+        dt: N,
+        gravity: Option<&Vector<N>>,
+        max_density_error: N,
+        max_iter: usize,
+    ) -> usize {
+        self.relax_with_coupling(dt, gravity, max_density_error, max_iter, &mut ())
+    }
+
+    /// Relaxes the fluids of this world so they start at rest, taking into account coupling with an external rigid-body engine.
+    ///
+    /// The coupled boundaries are updated at each iteration, but no force is transmitted to the coupled bodies.
+    /// See `self.relax` for details.
+    pub fn relax_with_coupling(
+        &mut self,
+        dt: N,
+        gravity: Option<&Vector<N>>,
+        max_density_error: N,
+        max_iter: usize,
+        coupling: &mut impl CouplingManager<N>,
+    ) -> usize {
+        let gravity = gravity.cloned().unwrap_or_else(Vector::zeros);
+        let mut num_iter = max_iter;
+
+        for i in 0..max_iter {
+            self.counters.reset();
+            self.timestep_manager.reset(dt);
+            self.solver.init_with_fluids(self.fluids.as_slice());
 
-            self.counters.cd.grid_insertion_time.resume();
-            geometry::insert_boundaries_to_grid(self.boundaries.as_slice(), &mut self.hgrid);
-            self.counters.cd.grid_insertion_time.pause();
+            for fluid in self.fluids.as_mut_slice() {
+                fluid.apply_particles_removal();
+            }
 
-            self.solver.init_with_boundaries(self.boundaries.as_slice());
+            while !self.timestep_manager.is_done() {
+                self.substep(&gravity, coupling, true);
+            }
+
+            for fluid in self.fluids.as_mut_slice() {
+                fluid.velocities.iter_mut().for_each(|v| v.fill(N::zero()));
+                fluid
+                    .accelerations
+                    .iter_mut()
+                    .for_each(|a| a.fill(N::zero()));
+            }
+
+            if self.density_error() <= max_density_error {
+                num_iter = i + 1;
+                break;
+            }
+        }
+
+        num_iter
+    }
+
+    // The largest average compression among the fluids, as computed by the last density computation.
+    fn density_error(&self) -> N {
+        let mut max_error = N::zero();
+
+        for (fluid, densities) in self
+            .fluids
+            .as_slice()
+            .iter()
+            .zip(self.solver.densities().iter())
+        {
+            let mut err = N::zero();
+
+            for (i, density) in densities.iter().enumerate() {
+                let density0 = fluid.particle_density0(i);
+
+                if *density > density0 {
+                    err += *density / density0 - N::one();
+                }
+            }
+
+            if !densities.is_empty() {
+                max_error = max_error.max(err / na::convert(densities.len() as f64));
+            }
+        }
+
+        max_error
+    }
+
+    fn update_kinematic_boundaries(&mut self, relaxing: bool) {
+        if relaxing {
+            // NOTE: the kinematic boundaries must not move while relaxing.
+            for boundary in self.boundaries.as_mut_slice() {
+                boundary.update_motion(self.time, self.timestep_manager.remaining_time());
+                boundary
+                    .velocities
+                    .iter_mut()
+                    .for_each(|v| v.fill(N::zero()));
+            }
+
+            return;
+        }
+
+        let t = self.time + self.timestep_manager.elapsed_time();
+        // NOTE: the length of the next substep is not known yet, so use the length of
+        // the last one, or the remaining time if this is the first substep.
+        let dt = if self.timestep_manager.dt().is_zero() {
+            self.timestep_manager.remaining_time()
+        } else {
+            self.timestep_manager.dt()
+        };
+
+        for boundary in self.boundaries.as_mut_slice() {
+            boundary.update_motion(t, dt);
+        }
+    }
+
+    fn substep(
+        &mut self,
+        gravity: &Vector<N>,
+        coupling: &mut impl CouplingManager<N>,
+        relaxing: bool,
+    ) {
+        self.nsubsteps_since_sort += 1;
+        self.counters.nsubsteps += 1;
+
+        self.counters.stages.collision_detection_time.resume();
+        self.counters.cd.grid_insertion_time.resume();
+        self.hgrid.clear();
+        geometry::insert_fluids_to_grid(self.fluids.as_slice(), &mut self.hgrid);
+        self.counters.cd.grid_insertion_time.pause();
+
+        self.counters.cd.boundary_update_time.resume();
+        self.update_kinematic_boundaries(relaxing);
+        coupling.update_boundaries(
+            &self.timestep_manager,
+            self.h,
+            self.particle_radius,
+            &self.hgrid,
+            self.fluids.as_mut_slice(),
+            &mut self.boundaries,
+        );
+
+        for boundary in self.boundaries.as_mut_slice() {
+            boundary.prepare_force_measurement();
+        }
+        self.counters.cd.boundary_update_time.pause();
 
-            self.contact_manager.update_contacts(
+        self.counters.cd.grid_insertion_time.resume();
+        geometry::insert_boundaries_to_grid(self.boundaries.as_slice(), &mut self.hgrid);
+        self.counters.cd.grid_insertion_time.pause();
+
+        self.solver.init_with_boundaries(self.boundaries.as_slice());
+
+        self.contact_manager.update_contacts(
+            &mut self.counters,
+            self.h,
+            self.fluids.as_slice(),
+            self.boundaries.as_slice(),
+            &self.hgrid,
+        );
+
+        self.counters.cd.ncontacts = self.contact_manager.ncontacts();
+        self.counters.stages.collision_detection_time.pause();
+
+        self.counters.stages.solver_time.resume();
+        self.solver.evaluate_kernels(
+            self.h,
+            &mut self.contact_manager,
+            self.fluids.as_slice(),
+            self.boundaries.as_slice(),
+        );
+
+        self.solver.compute_densities(
+            &self.contact_manager,
+            self.fluids.as_slice(),
+            self.boundaries.as_mut_slice(),
+        );
+
+        self.counters.solver.non_pressure_resolution_time.resume();
+        for force in &mut self.multiphase_forces {
+            force.solve(
                 &mut self.counters,
                 self.h,
-                self.fluids.as_slice(),
+                &self.contact_manager,
+                self.fluids.as_mut_slice(),
                 self.boundaries.as_slice(),
-                &self.hgrid,
+                self.solver.densities(),
             );
+        }
+        self.counters.solver.non_pressure_resolution_time.pause();
 
-            self.counters.cd.ncontacts = self.contact_manager.ncontacts();
-            self.counters.stages.collision_detection_time.pause();
+        self.solver.step(
+            &mut self.counters,
+            &mut self.timestep_manager,
+            gravity,
+            &mut self.contact_manager,
+            self.h,
+            self.fluids.as_mut_slice(),
+            self.boundaries.as_slice(),
+        );
 
-            self.counters.stages.solver_time.resume();
-            self.solver.evaluate_kernels(
-                self.h,
-                &mut self.contact_manager,
-                self.fluids.as_slice(),
-                self.boundaries.as_slice(),
-            );
+        // NOTE: relaxing must only change the particle positions.
+        if !relaxing {
+            self.counters.solver.non_pressure_resolution_time.resume();
+            for force in &mut self.multiphase_forces {
+                force.update(
+                    &mut self.counters,
+                    &self.timestep_manager,
+                    self.h,
+                    &self.contact_manager,
+                    self.fluids.as_mut_slice(),
+                    self.boundaries.as_slice(),
+                    self.solver.densities(),
+                );
+            }
+            self.counters.solver.non_pressure_resolution_time.pause();
 
-            self.solver.compute_densities(
+            self.heat_transfer.transfer_heat(
+                &self.timestep_manager,
+                self.h,
                 &self.contact_manager,
-                self.fluids.as_slice(),
+                self.fluids.as_mut_slice(),
                 self.boundaries.as_mut_slice(),
+                self.solver.densities(),
             );
 
-            self.solver.step(
-                &mut self.counters,
-                &mut self.timestep_manager,
-                gravity,
-                &mut self.contact_manager,
-                self.h,
-                self.fluids.as_mut_slice(),
-                self.boundaries.as_slice(),
-            );
+            if let Some(diffuse_particles) = &mut self.diffuse_particles {
+                diffuse_particles.update(
+                    &self.timestep_manager,
+                    self.h,
+                    gravity,
+                    &self.contact_manager,
+                    &self.hgrid,
+                    self.fluids.as_slice(),
+                    self.boundaries.as_slice(),
+                    self.solver.densities(),
+                );
+            }
 
-            coupling.transmit_forces(&self.boundaries);
-            self.counters.stages.solver_time.pause();
-        }
+            let dt = self.timestep_manager.dt();
 
-        //        if self.nsubsteps_since_sort >= 100 {
-        //            self.nsubsteps_since_sort = 0;
-        //            println!("Performing z-sort of particles.");
-        //            par_iter_mut!(self.fluids.as_mut_slice()).for_each(|fluid| fluid.z_sort())
-        //        }
+            for boundary in self.boundaries.as_mut_slice() {
+                boundary.accumulate_force_measurement(dt);
+            }
 
-        self.counters.step_time.pause();
-        //        println!("Counters: {}", self.counters);
+            coupling.transmit_forces(&self.timestep_manager, &self.boundaries);
+        }
+        self.counters.stages.solver_time.pause();
+    }
+
+    /// The simulation time, i.e., the sum of the lengths of all the timesteps executed by `self.step`.
+    pub fn time(&self) -> N {
+        self.time
     }
 
     /// Add a fluid to the liquid world.
@@ -162,6 +383,42 @@ impl<N: RealField> LiquidWorld<N> {
         self.boundaries.insert(boundary)
     }
 
+    /// Add a multiphase force to the liquid world.
+    ///
+    /// Multiphase forces are applied to all the fluids of this world at each substep.
+    pub fn add_multiphase_force(&mut self, force: impl MultiphaseForce<N> + 'static) {
+        self.multiphase_forces.push(Box::new(force))
+    }
+
+    /// Enables the generation of diffuse particles (spray, foam, and bubbles) with the given model.
+    ///
+    /// This replaces the diffuse particles model previously enabled, if any.
+    pub fn enable_diffuse_particles(&mut self, model: Ihmsen2012DiffuseParticles<N>) {
+        self.diffuse_particles = Some(model)
+    }
+
+    /// Disables the generation of diffuse particles, and returns the model previously enabled, if any.
+    pub fn disable_diffuse_particles(&mut self) -> Option<Ihmsen2012DiffuseParticles<N>> {
+        self.diffuse_particles.take()
+    }
+
+    /// The diffuse particles model of this liquid world, if diffuse particles are enabled.
+    pub fn diffuse_particles_model(&self) -> Option<&Ihmsen2012DiffuseParticles<N>> {
+        self.diffuse_particles.as_ref()
+    }
+
+    /// The mutable diffuse particles model of this liquid world, if diffuse particles are enabled.
+    pub fn diffuse_particles_model_mut(&mut self) -> Option<&mut Ihmsen2012DiffuseParticles<N>> {
+        self.diffuse_particles.as_mut()
+    }
+
+    /// The diffuse particles of this liquid world, if diffuse particles are enabled.
+    pub fn diffuse_particles(&self) -> Option<&DiffuseParticles<N>> {
+        self.diffuse_particles
+            .as_ref()
+            .map(|model| model.particles())
+    }
+
     /// Add a fluid to the liquid world.
     pub fn remove_fluid(&mut self, handle: FluidHandle) -> Option<Fluid<N>> {
         self.fluids.remove(handle)
@@ -192,6 +449,18 @@ impl<N: RealField> LiquidWorld<N> {
         &mut self.boundaries
     }
 
+    /// The contacts detected between the particles during the last substep.
+    pub fn contact_manager(&self) -> &ContactManager<N> {
+        &self.contact_manager
+    }
+
+    /// The densities of the fluid particles computed during the last substep.
+    ///
+    /// The `i`-th element contains the densities of the particles of the `i`-th fluid of `self.fluids().as_slice()`.
+    pub fn densities(&self) -> &[Vec<N>] {
+        self.solver.densities()
+    }
+
     /// The SPH kernel radius.
     pub fn h(&self) -> N {
         self.h
@@ -202,3 +471,40 @@ impl<N: RealField> LiquidWorld<N> {
         self.particle_radius
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::LiquidWorld;
+    use crate::math::{AngularVector, Isometry, Point, Vector};
+    use crate::object::{Boundary, BoundaryMotion, Fluid};
+    use crate::solver::DFSPHSolver;
+
+    #[test]
+    fn relaxing_only_moves_particles() {
+        let mut world = LiquidWorld::new(DFSPHSolver::<f64>::new(), 0.025, 2.0);
+        let mut fluid = Fluid::new(
+            vec![Point::origin(), Point::from(Vector::x() * 0.04)],
+            0.025,
+            1000.0,
+        );
+        fluid.thermal_conductivity = 1.0;
+        fluid.temperatures = vec![0.0, 100.0];
+        let fluid = world.add_fluid(fluid);
+
+        let motion =
+            BoundaryMotion::velocity(Isometry::identity(), Vector::x(), AngularVector::zeros());
+        let boundary_position = Point::from(Vector::y() * 10.0);
+        let boundary = world.add_boundary(Boundary::new_kinematic(vec![boundary_position], motion));
+
+        let gravity = Vector::y() * -9.81;
+        assert_eq!(world.relax(0.01, Some(&gravity), -1.0, 5), 5);
+
+        let fluid = world.fluids().get(fluid).unwrap();
+        assert_eq!(fluid.temperatures, vec![0.0, 100.0]);
+        assert!(fluid.positions[1].y < 0.0);
+
+        let boundary = world.boundaries().get(boundary).unwrap();
+        assert_eq!(boundary.positions, vec![boundary_position]);
+        assert_eq!(boundary.velocities, vec![Vector::zeros()]);
+    }
+}
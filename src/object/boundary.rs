@@ -1,11 +1,40 @@
-use crate::math::{Isometry, Point, Vector};
-use crate::object::{ContiguousArena, ContiguousArenaIndex};
+use crate::geometry::InteractionGroups;
+use crate::math::{self, AngularVector, Isometry, Point, Vector};
+use crate::object::{BoundaryMotion, ContiguousArena, ContiguousArenaIndex};
 use na::{self, RealField};
 use std::sync::RwLock;
 
+/// The temperature model of a boundary object.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum BoundaryTemperature<N: RealField> {
+    /// The boundary does not exchange any heat with fluids.
+    Insulated,
+    /// The boundary has a fixed temperature, i.e., it behaves as an infinite heat source or sink.
+    Fixed(N),
+    /// The boundary has a uniform temperature modified by the heat exchanged with fluids.
+    Conducted {
+        /// The current temperature of the boundary.
+        temperature: N,
+        /// The heat capacity of the whole boundary object.
+        heat_capacity: N,
+    },
+}
+
+impl<N: RealField> BoundaryTemperature<N> {
+    /// The current temperature of the boundary, or `None` if it is insulated.
+    pub fn temperature(&self) -> Option<N> {
+        match self {
+            BoundaryTemperature::Insulated => None,
+            BoundaryTemperature::Fixed(t) => Some(*t),
+            BoundaryTemperature::Conducted { temperature, .. } => Some(*temperature),
+        }
+    }
+}
+
 /// A boundary object.
 ///
-/// A boundary object is composed of static particles, or of particles coupled with non-fluid bodies.
+/// A boundary object is composed of static particles, of particles coupled with non-fluid bodies,
+/// or of kinematic particles moved by a prescribed motion.
 pub struct Boundary<N: RealField> {
     /// The world-space position of the boundary particles.
     pub positions: Vec<Point<N>>,
@@ -17,6 +46,33 @@ pub struct Boundary<N: RealField> {
     /// If this is set to `None` (which is the default), the boundary won't receive any
     /// force for fluids.
     pub forces: Option<RwLock<Vec<Vector<N>>>>,
+    /// Whether the forces applied by the fluids on this boundary are measured even if it is not coupled with a body.
+    ///
+    /// If this is `true`, `self.forces` is allocated and cleared automatically at each substep, so
+    /// `self.hydrodynamic_force()` and `self.hydrodynamic_torque(...)` also work for static and kinematic
+    /// boundaries. Defaults to `false`.
+    pub measure_forces: bool,
+    /// The friction coefficient of this boundary.
+    ///
+    /// This multiplies the boundary viscosity coefficient of the viscosity forces of the fluids
+    /// interacting with this boundary. Set it to zero for a free-slip boundary. Defaults to 1.0.
+    pub friction: N,
+    /// The adhesion strength of this boundary.
+    ///
+    /// This multiplies the boundary coefficient of the surface tension forces of the fluids
+    /// interacting with this boundary. Set it to zero for a non-wetting boundary. Defaults to 1.0.
+    pub adhesion: N,
+    /// The groups used to filter the interactions of this boundary with fluids.
+    pub interaction_groups: InteractionGroups,
+    /// The thermal conductivity of this boundary.
+    pub thermal_conductivity: N,
+    /// The temperature model of this boundary.
+    pub temperature: BoundaryTemperature<N>,
+    local_positions: Vec<Point<N>>,
+    motion: Option<BoundaryMotion<N>>,
+    force_integral: Vector<N>,
+    moment_integral: AngularVector<N>,
+    measurement_duration: N,
 }
 
 impl<N: RealField> Boundary<N> {
@@ -33,6 +89,73 @@ impl<N: RealField> Boundary<N> {
             velocities,
             volumes,
             forces: None,
+            measure_forces: false,
+            friction: N::one(),
+            adhesion: N::one(),
+            interaction_groups: InteractionGroups::default(),
+            thermal_conductivity: N::one(),
+            temperature: BoundaryTemperature::Insulated,
+            local_positions: Vec::new(),
+            motion: None,
+            force_integral: Vector::zeros(),
+            moment_integral: AngularVector::zeros(),
+            measurement_duration: N::zero(),
+        }
+    }
+
+    /// Initialize a kinematic boundary object with the given particles and motion.
+    ///
+    /// The particle positions are expressed in the local frame of the boundary. At each substep,
+    /// the world-space positions and velocities of the particles are updated from the pose given
+    /// by `motion`.
+    pub fn new_kinematic(local_positions: Vec<Point<N>>, motion: BoundaryMotion<N>) -> Self {
+        let mut result = Self::new(local_positions.clone());
+        result.local_positions = local_positions;
+        result.motion = Some(motion);
+        result.update_motion(N::zero(), N::zero());
+        result
+    }
+
+    /// Is this boundary object moved by a prescribed motion?
+    pub fn is_kinematic(&self) -> bool {
+        self.motion.is_some()
+    }
+
+    /// The motion of this boundary object, if it is kinematic.
+    pub fn motion(&self) -> Option<&BoundaryMotion<N>> {
+        self.motion.as_ref()
+    }
+
+    /// A mutable reference to the motion of this boundary object, if it is kinematic.
+    pub fn motion_mut(&mut self) -> Option<&mut BoundaryMotion<N>> {
+        self.motion.as_mut()
+    }
+
+    /// The local-space positions of the particles of this boundary object, if it is kinematic.
+    pub fn local_positions(&self) -> &[Point<N>] {
+        &self.local_positions
+    }
+
+    /// Sets the world-space positions and velocities of the particles of a kinematic boundary
+    /// to their values at the time `t`.
+    ///
+    /// The time increment `dt` is used to estimate velocities of motions given by closures.
+    /// This does nothing if this boundary is not kinematic.
+    pub(crate) fn update_motion(&mut self, t: N, dt: N) {
+        if let Some(motion) = &self.motion {
+            let pose = motion.pose_at(t);
+            let (linvel, angvel) = motion.velocity_at(t, dt);
+
+            for ((pos, vel), local_pos) in self
+                .positions
+                .iter_mut()
+                .zip(self.velocities.iter_mut())
+                .zip(self.local_positions.iter())
+            {
+                *pos = pose * local_pos;
+                *vel =
+                    linvel + math::angular_cross(&angvel, &(pos.coords - pose.translation.vector));
+            }
         }
     }
 
@@ -56,6 +179,63 @@ impl<N: RealField> Boundary<N> {
         }
     }
 
+    /// The resultant of the forces applied by the fluids on this boundary, averaged over the substeps of the last timestep.
+    ///
+    /// This is zero if `self.forces` was `None` during the last timestep.
+    pub fn hydrodynamic_force(&self) -> Vector<N> {
+        if self.measurement_duration.is_zero() {
+            Vector::zeros()
+        } else {
+            self.force_integral / self.measurement_duration
+        }
+    }
+
+    /// The resultant torque about `point` of the forces applied by the fluids on this boundary, averaged
+    /// over the substeps of the last timestep.
+    ///
+    /// This is zero if `self.forces` was `None` during the last timestep.
+    pub fn hydrodynamic_torque(&self, point: &Point<N>) -> AngularVector<N> {
+        if self.measurement_duration.is_zero() {
+            AngularVector::zeros()
+        } else {
+            // The torque about the origin, moved to `point`.
+            (self.moment_integral - math::gcross_matrix(&point.coords) * self.force_integral)
+                / self.measurement_duration
+        }
+    }
+
+    // Allocates and clears the force buffer of this boundary if its forces are measured.
+    pub(crate) fn prepare_force_measurement(&mut self) {
+        if self.measure_forces {
+            if self.forces.is_none() {
+                self.forces = Some(RwLock::new(Vec::new()));
+            }
+
+            self.clear_forces(true);
+        }
+    }
+
+    // Resets the time-averaged resultant force and torque at the beginning of a timestep.
+    pub(crate) fn reset_force_measurement(&mut self) {
+        self.force_integral = Vector::zeros();
+        self.moment_integral = AngularVector::zeros();
+        self.measurement_duration = N::zero();
+    }
+
+    // Accumulates the forces applied by the fluids during a substep of length `dt`.
+    pub(crate) fn accumulate_force_measurement(&mut self, dt: N) {
+        if let Some(forces) = &self.forces {
+            let forces = forces.read().unwrap();
+
+            for (position, force) in self.positions.iter().zip(forces.iter()) {
+                self.force_integral += force * dt;
+                self.moment_integral += math::gcross_matrix(&position.coords) * force * dt;
+            }
+
+            self.measurement_duration += dt;
+        }
+    }
+
     /// Clears all the forces applied to this boundary object's particles.
     pub fn clear_forces(&mut self, resize_buffer: bool) {
         if let Some(forces) = &mut self.forces {
@@ -85,9 +265,9 @@ impl From<ContiguousArenaIndex> for BoundaryHandle {
     }
 }
 
-impl Into<ContiguousArenaIndex> for BoundaryHandle {
+impl From<BoundaryHandle> for ContiguousArenaIndex {
     #[inline]
-    fn into(self) -> ContiguousArenaIndex {
-        self.0
+    fn from(handle: BoundaryHandle) -> Self {
+        handle.0
     }
 }
@@ -0,0 +1,138 @@
+use crate::math::{AngularVector, Isometry, Rotation, Translation, Vector};
+use na::{self, RealField};
+
+/// The motion of a kinematic boundary object.
+///
+/// A motion gives the pose of the boundary at any time. The boundary particles are moved rigidly
+/// by this pose, and rotate around the origin of the boundary local frame.
+pub enum BoundaryMotion<N: RealField> {
+    /// A track of keyframes `(time, pose)`, sorted by increasing time.
+    ///
+    /// The pose is linearly interpolated between two consecutive keyframes. Before the first
+    /// keyframe (resp. after the last keyframe), the boundary stays at rest at the first
+    /// (resp. last) pose.
+    Keyframes(Vec<(N, Isometry<N>)>),
+    /// A closure computing the pose of the boundary at the given time.
+    ///
+    /// The velocities of the boundary are estimated by finite differences.
+    Closure(Box<dyn Fn(N) -> Isometry<N> + Send + Sync>),
+    /// A motion with constant linear and angular velocities.
+    Velocity {
+        /// The pose of the boundary at time zero.
+        pose0: Isometry<N>,
+        /// The linear velocity of the origin of the boundary local frame.
+        linvel: Vector<N>,
+        /// The angular velocity of the boundary around the origin of its local frame.
+        angvel: AngularVector<N>,
+    },
+}
+
+impl<N: RealField> BoundaryMotion<N> {
+    /// Initializes a motion from the given keyframes `(time, pose)`.
+    ///
+    /// The keyframes are sorted by increasing time.
+    pub fn keyframes(mut keyframes: Vec<(N, Isometry<N>)>) -> Self {
+        keyframes.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+        BoundaryMotion::Keyframes(keyframes)
+    }
+
+    /// Initializes a motion from a closure computing the pose of the boundary at a given time.
+    pub fn closure(f: impl Fn(N) -> Isometry<N> + Send + Sync + 'static) -> Self {
+        BoundaryMotion::Closure(Box::new(f))
+    }
+
+    /// Initializes a motion with constant linear and angular velocities.
+    pub fn velocity(pose0: Isometry<N>, linvel: Vector<N>, angvel: AngularVector<N>) -> Self {
+        BoundaryMotion::Velocity {
+            pose0,
+            linvel,
+            angvel,
+        }
+    }
+
+    /// The pose of the boundary at the time `t`.
+    pub fn pose_at(&self, t: N) -> Isometry<N> {
+        match self {
+            BoundaryMotion::Keyframes(keyframes) => match Self::keyframe_segment(keyframes, t) {
+                KeyframeSegment::Static(pose) => pose,
+                KeyframeSegment::Moving(k0, k1) => {
+                    let s = (t - k0.0) / (k1.0 - k0.0);
+                    let (linvel, angvel) = Self::relative_velocity(&k0.1, &k1.1, k1.0 - k0.0);
+                    Self::integrate(&k0.1, &linvel, &angvel, s * (k1.0 - k0.0))
+                }
+            },
+            BoundaryMotion::Closure(f) => f(t),
+            BoundaryMotion::Velocity {
+                pose0,
+                linvel,
+                angvel,
+            } => Self::integrate(pose0, linvel, angvel, t),
+        }
+    }
+
+    /// The linear and angular velocities of the boundary at the time `t`.
+    ///
+    /// The linear velocity is the velocity of the origin of the boundary local frame. If this motion
+    /// is given by a closure, the velocities are estimated by finite differences with the time
+    /// increment `dt`.
+    pub fn velocity_at(&self, t: N, dt: N) -> (Vector<N>, AngularVector<N>) {
+        match self {
+            BoundaryMotion::Keyframes(keyframes) => match Self::keyframe_segment(keyframes, t) {
+                KeyframeSegment::Static(_) => (Vector::zeros(), AngularVector::zeros()),
+                KeyframeSegment::Moving(k0, k1) => {
+                    Self::relative_velocity(&k0.1, &k1.1, k1.0 - k0.0)
+                }
+            },
+            BoundaryMotion::Closure(f) => {
+                if dt.is_zero() {
+                    (Vector::zeros(), AngularVector::zeros())
+                } else {
+                    Self::relative_velocity(&f(t), &f(t + dt), dt)
+                }
+            }
+            BoundaryMotion::Velocity { linvel, angvel, .. } => (*linvel, *angvel),
+        }
+    }
+
+    fn keyframe_segment(keyframes: &[(N, Isometry<N>)], t: N) -> KeyframeSegment<N> {
+        match (keyframes.first(), keyframes.last()) {
+            (None, _) | (_, None) => KeyframeSegment::Static(Isometry::identity()),
+            (Some(first), _) if t <= first.0 => KeyframeSegment::Static(first.1),
+            (_, Some(last)) if t >= last.0 => KeyframeSegment::Static(last.1),
+            _ => {
+                // Index of the first keyframe strictly after `t`.
+                let i1 = keyframes.iter().position(|k| k.0 > t).unwrap();
+                KeyframeSegment::Moving(keyframes[i1 - 1], keyframes[i1])
+            }
+        }
+    }
+
+    // The constant velocities that move `pose0` to `pose1` in the time `dt`.
+    fn relative_velocity(
+        pose0: &Isometry<N>,
+        pose1: &Isometry<N>,
+        dt: N,
+    ) -> (Vector<N>, AngularVector<N>) {
+        let inv_dt = N::one() / dt;
+        let linvel = (pose1.translation.vector - pose0.translation.vector) * inv_dt;
+        let angvel = (pose1.rotation * pose0.rotation.inverse()).scaled_axis() * inv_dt;
+        (linvel, angvel)
+    }
+
+    // The pose reached after moving `pose0` with constant velocities during the time `t`.
+    fn integrate(
+        pose0: &Isometry<N>,
+        linvel: &Vector<N>,
+        angvel: &AngularVector<N>,
+        t: N,
+    ) -> Isometry<N> {
+        let translation = Translation::from(pose0.translation.vector + linvel * t);
+        let rotation = Rotation::from_scaled_axis(angvel * t) * pose0.rotation;
+        Isometry::from_parts(translation, rotation)
+    }
+}
+
+enum KeyframeSegment<N: RealField> {
+    Static(Isometry<N>),
+    Moving((N, Isometry<N>), (N, Isometry<N>)),
+}
@@ -0,0 +1,90 @@
+use crate::math::{Point, Vector};
+use na::RealField;
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+/// The kind of a diffuse particle, depending on its location relative to the fluid.
+pub enum DiffuseParticleKind {
+    /// A particle with few fluid neighbors, e.g., in the air above a wave crest.
+    Spray,
+    /// A particle at the surface of the fluid.
+    Foam,
+    /// A particle with many fluid neighbors, i.e., an air bubble inside of the fluid.
+    Bubble,
+}
+
+/// A set of diffuse particles (spray, foam, and bubbles) used for secondary whitewater effects.
+///
+/// Diffuse particles are generated and advected by a model like `Ihmsen2012DiffuseParticles`.
+/// They are not part of any fluid, so they do not take part in the pressure resolution and
+/// do not affect the fluids.
+pub struct DiffuseParticles<N: RealField> {
+    /// The world-space position of the diffuse particles.
+    pub positions: Vec<Point<N>>,
+    /// The velocities of the diffuse particles.
+    pub velocities: Vec<Vector<N>>,
+    /// The kind of each diffuse particle.
+    pub kinds: Vec<DiffuseParticleKind>,
+    /// The remaining lifetime of each diffuse particle.
+    pub lifetimes: Vec<N>,
+}
+
+impl<N: RealField> DiffuseParticles<N> {
+    /// Initializes an empty set of diffuse particles.
+    pub fn new() -> Self {
+        Self {
+            positions: Vec::new(),
+            velocities: Vec::new(),
+            kinds: Vec::new(),
+            lifetimes: Vec::new(),
+        }
+    }
+
+    /// The number of diffuse particles on this set.
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    /// Returns `true` if this set does not contain any diffuse particle.
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    /// Removes all the diffuse particles from this set.
+    pub fn clear(&mut self) {
+        self.positions.clear();
+        self.velocities.clear();
+        self.kinds.clear();
+        self.lifetimes.clear();
+    }
+
+    pub(crate) fn push(
+        &mut self,
+        position: Point<N>,
+        velocity: Vector<N>,
+        kind: DiffuseParticleKind,
+        lifetime: N,
+    ) {
+        self.positions.push(position);
+        self.velocities.push(velocity);
+        self.kinds.push(kind);
+        self.lifetimes.push(lifetime);
+    }
+
+    // Removes all the particles with a non-positive lifetime.
+    pub(crate) fn remove_dead_particles(&mut self) {
+        let mask: Vec<_> = self.lifetimes.iter().map(|l| *l <= N::zero()).collect();
+
+        if mask.iter().any(|dead| *dead) {
+            crate::helper::filter_from_mask(&mask, &mut self.positions);
+            crate::helper::filter_from_mask(&mask, &mut self.velocities);
+            crate::helper::filter_from_mask(&mask, &mut self.kinds);
+            crate::helper::filter_from_mask(&mask, &mut self.lifetimes);
+        }
+    }
+}
+
+impl<N: RealField> Default for DiffuseParticles<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
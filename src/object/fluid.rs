@@ -1,3 +1,4 @@
+use crate::geometry::InteractionGroups;
 use crate::math::{Isometry, Point, Vector};
 use crate::object::{ContiguousArena, ContiguousArenaIndex};
 use crate::solver::NonPressureForce;
@@ -19,6 +20,32 @@ pub struct Fluid<N: RealField> {
     pub volumes: Vec<N>,
     /// The rest density of this fluid.
     pub density0: N,
+    /// The rest density of each particle of this fluid.
+    ///
+    /// If this is empty (which is the default), every particle has the rest density `self.density0`.
+    /// This is set by multiphase models like `Ren2014Multiphase` to the rest density of the mixture
+    /// at each particle.
+    pub particle_densities0: Vec<N>,
+    /// The volume fraction of each phase of a multiphase model, for each particle of this fluid.
+    ///
+    /// The volume fractions of a particle are empty if this fluid is not part of a multiphase model.
+    pub volume_fractions: Vec<Vec<N>>,
+    /// The volume fractions given to the particles added with `self.add_particles`.
+    pub volume_fractions0: Vec<N>,
+    /// The pressure of the fluid particles, as computed by the pressure solver during the last timestep.
+    pub pressures: Vec<N>,
+    /// The temperature of the fluid particles.
+    pub temperatures: Vec<N>,
+    /// The temperature given to the particles added with `self.add_particles`.
+    pub temperature0: N,
+    /// The thermal conductivity of this fluid.
+    ///
+    /// Heat is not exchanged by this fluid if this is zero (which is the default).
+    pub thermal_conductivity: N,
+    /// The specific heat capacity of this fluid.
+    pub specific_heat_capacity: N,
+    /// The groups used to filter the interactions of this fluid with other fluids and boundaries.
+    pub interaction_groups: InteractionGroups,
     /// Mask indicating what particles have been deleted.
     deleted_particles: Vec<bool>,
     /// Indicates if a bit of the `deleted_particles` mask has been set.
@@ -55,6 +82,15 @@ impl<N: RealField> Fluid<N> {
             deleted_particles: std::iter::repeat(false).take(num_particles).collect(),
             num_deleted_particles: 0,
             density0,
+            particle_densities0: Vec::new(),
+            volume_fractions: std::iter::repeat(Vec::new()).take(num_particles).collect(),
+            volume_fractions0: Vec::new(),
+            pressures: std::iter::repeat(N::zero()).take(num_particles).collect(),
+            temperatures: std::iter::repeat(N::zero()).take(num_particles).collect(),
+            temperature0: N::zero(),
+            thermal_conductivity: N::zero(),
+            specific_heat_capacity: N::one(),
+            interaction_groups: InteractionGroups::default(),
             particle_radius,
         }
     }
@@ -79,10 +115,25 @@ impl<N: RealField> Fluid<N> {
 
     pub(crate) fn apply_particles_removal(&mut self) {
         if self.num_deleted_particles != 0 {
+            for forces in &mut self.nonpressure_forces {
+                forces.apply_particles_removal(&self.deleted_particles);
+            }
+
             crate::helper::filter_from_mask(&self.deleted_particles, &mut self.positions);
             crate::helper::filter_from_mask(&self.deleted_particles, &mut self.velocities);
             crate::helper::filter_from_mask(&self.deleted_particles, &mut self.accelerations);
             crate::helper::filter_from_mask(&self.deleted_particles, &mut self.volumes);
+            crate::helper::filter_from_mask(&self.deleted_particles, &mut self.pressures);
+            crate::helper::filter_from_mask(&self.deleted_particles, &mut self.temperatures);
+            crate::helper::filter_from_mask(&self.deleted_particles, &mut self.volume_fractions);
+
+            if !self.particle_densities0.is_empty() {
+                crate::helper::filter_from_mask(
+                    &self.deleted_particles,
+                    &mut self.particle_densities0,
+                );
+            }
+
             self.deleted_particles.truncate(self.positions.len());
             self.deleted_particles.iter_mut().for_each(|i| *i = false);
             self.num_deleted_particles = 0;
@@ -134,6 +185,15 @@ impl<N: RealField> Fluid<N> {
 
         self.accelerations.resize(nparticles, Vector::zeros());
         self.volumes.resize(nparticles, particle_volume);
+        self.pressures.resize(nparticles, N::zero());
+        self.temperatures.resize(nparticles, self.temperature0);
+        self.volume_fractions
+            .resize(nparticles, self.volume_fractions0.clone());
+
+        if !self.particle_densities0.is_empty() {
+            self.particle_densities0.resize(nparticles, self.density0);
+        }
+
         self.deleted_particles.resize(nparticles, false);
     }
 
@@ -144,12 +204,32 @@ impl<N: RealField> Fluid<N> {
         self.velocities = crate::z_order::apply_permutation(&order, &self.velocities);
         self.accelerations = crate::z_order::apply_permutation(&order, &self.accelerations);
         self.volumes = crate::z_order::apply_permutation(&order, self.volumes.as_slice());
+        self.pressures = crate::z_order::apply_permutation(&order, &self.pressures);
+        self.temperatures = crate::z_order::apply_permutation(&order, &self.temperatures);
+        self.volume_fractions = crate::z_order::apply_permutation(&order, &self.volume_fractions);
+
+        if !self.particle_densities0.is_empty() {
+            self.particle_densities0 =
+                crate::z_order::apply_permutation(&order, &self.particle_densities0);
+        }
 
         for forces in &mut self.nonpressure_forces {
             forces.apply_permutation(&order);
         }
     }
 
+    /// Sets the volume fractions of all the particles of this fluid, as well as the volume fractions
+    /// given to the particles added afterward with `self.add_particles`.
+    ///
+    /// The `i`-th element of `volume_fractions` is the volume fraction of the `i`-th phase of the
+    /// multiphase model this fluid is simulated with.
+    pub fn set_volume_fractions(&mut self, volume_fractions: &[N]) {
+        self.volume_fractions0 = volume_fractions.to_vec();
+        self.volume_fractions
+            .iter_mut()
+            .for_each(|f| *f = volume_fractions.to_vec());
+    }
+
     /// Apply the given transformation to each particle of this fluid.
     pub fn transform_by(&mut self, t: &Isometry<N>) {
         self.positions.iter_mut().for_each(|p| *p = t * *p)
@@ -167,9 +247,18 @@ impl<N: RealField> Fluid<N> {
         bounding_volume::local_point_cloud_aabb(&self.positions).loosened(particle_radius)
     }
 
+    /// The rest density of the `i`-th particle of this fluid.
+    pub fn particle_density0(&self, i: usize) -> N {
+        if self.particle_densities0.is_empty() {
+            self.density0
+        } else {
+            self.particle_densities0[i]
+        }
+    }
+
     /// The mass of the `i`-th particle of this fluid.
     pub fn particle_mass(&self, i: usize) -> N {
-        self.volumes[i] * self.density0
+        self.volumes[i] * self.particle_density0(i)
     }
 
     /// The inverse mass of the `i`-th particle of this fluid.
@@ -179,7 +268,7 @@ impl<N: RealField> Fluid<N> {
         if self.volumes[i].is_zero() {
             N::zero()
         } else {
-            N::one() / (self.volumes[i] * self.density0)
+            N::one() / (self.volumes[i] * self.particle_density0(i))
         }
     }
 }
@@ -197,9 +286,9 @@ impl From<ContiguousArenaIndex> for FluidHandle {
     }
 }
 
-impl Into<ContiguousArenaIndex> for FluidHandle {
+impl From<FluidHandle> for ContiguousArenaIndex {
     #[inline]
-    fn into(self) -> ContiguousArenaIndex {
-        self.0
+    fn from(handle: FluidHandle) -> Self {
+        handle.0
     }
 }
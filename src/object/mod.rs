@@ -1,9 +1,13 @@
 //! Fluid and boundary objects that can be simulated.
 
-pub use self::boundary::{Boundary, BoundaryHandle, BoundarySet};
+pub use self::boundary::{Boundary, BoundaryHandle, BoundarySet, BoundaryTemperature};
+pub use self::boundary_motion::BoundaryMotion;
 pub use self::contiguous_arena::{ContiguousArena, ContiguousArenaIndex};
+pub use self::diffuse_particles::{DiffuseParticleKind, DiffuseParticles};
 pub use self::fluid::{Fluid, FluidHandle, FluidSet};
 
 mod boundary;
+mod boundary_motion;
 mod contiguous_arena;
+mod diffuse_particles;
 mod fluid;
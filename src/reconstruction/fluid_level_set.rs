@@ -0,0 +1,313 @@
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+use na::{self, RealField};
+
+use crate::geometry::HGrid;
+use crate::math::{Point, Vector, DIM};
+use crate::object::Fluid;
+
+/// A signed distance field sampled on a regular grid.
+#[derive(Clone, Debug)]
+pub struct SignedDistanceGrid<N: RealField> {
+    /// The position of the first grid point, i.e., the grid point with the smallest coordinates.
+    pub origin: Point<N>,
+    /// The distance between two adjacent grid points.
+    pub cell_width: N,
+    /// The number of grid points along each axis.
+    pub dimensions: [usize; DIM],
+    /// The signed distance at each grid point, where the index along the first axis varies the fastest.
+    pub values: Vec<N>,
+}
+
+impl<N: RealField> SignedDistanceGrid<N> {
+    /// The index in `self.values` of the grid point with the given integer coordinates.
+    pub fn index(&self, coords: [usize; DIM]) -> usize {
+        coords
+            .iter()
+            .zip(self.dimensions.iter())
+            .rev()
+            .fold(0, |acc, (c, dim)| acc * dim + c)
+    }
+
+    /// The position of the grid point with the given integer coordinates.
+    pub fn grid_point(&self, coords: [usize; DIM]) -> Point<N> {
+        let mut result = self.origin;
+
+        for (r, c) in result.coords.iter_mut().zip(coords.iter()) {
+            *r += self.cell_width * na::convert(*c as f64);
+        }
+
+        result
+    }
+
+    /// The signed distance at the given point, interpolated from the grid points around it.
+    ///
+    /// Returns `None` if the point is outside of the bounds of the grid.
+    pub fn sample(&self, point: &Point<N>) -> Option<N> {
+        let mut base = [0; DIM];
+        let mut t = Vector::zeros();
+
+        for k in 0..DIM {
+            if self.dimensions[k] == 0 {
+                return None;
+            }
+
+            let x = (point[k] - self.origin[k]) / self.cell_width;
+            let max = na::convert((self.dimensions[k] - 1) as f64);
+
+            if x < N::zero() || x > max {
+                return None;
+            }
+
+            // NOTE: points on the upper bound are interpolated in the last cell.
+            let floor = x.floor().min(max - N::one()).max(N::zero());
+            base[k] = na::try_convert::<N, f64>(floor).unwrap() as usize;
+            t[k] = (x - floor).min(N::one());
+        }
+
+        let mut result = N::zero();
+
+        for corner in 0..1 << DIM {
+            let mut coords = base;
+            let mut weight = N::one();
+
+            for k in 0..DIM {
+                if corner & (1 << k) != 0 {
+                    coords[k] = (coords[k] + 1).min(self.dimensions[k] - 1);
+                    weight *= t[k];
+                } else {
+                    weight *= N::one() - t[k];
+                }
+            }
+
+            if !weight.is_zero() {
+                result += self.values[self.index(coords)] * weight;
+            }
+        }
+
+        Some(result)
+    }
+}
+
+/// A level set of the volume occupied by some fluids, using the particle level set of Zhu and Bridson 2005.
+///
+/// The signed distance to the surface at a point is estimated from the particles around it: it is negative
+/// inside of the fluid, and positive outside. It is only accurate in a narrow band around the surface,
+/// and is clamped to `[-band_width, band_width]`. Only the particles closer than `band_width` to a point
+/// are used to evaluate the distance at this point, so the particles are stored into a spatial grid.
+///
+/// This level set is a snapshot of the particles at the time it was built: it must be rebuilt after each
+/// simulation step to follow the motion of the fluids.
+pub struct FluidLevelSet<N: RealField> {
+    band_width: N,
+    positions: Vec<Point<N>>,
+    radii: Vec<N>,
+    hgrid: HGrid<N, usize>,
+}
+
+impl<N: RealField> FluidLevelSet<N> {
+    /// Builds the level set of the volume occupied by the given fluids.
+    ///
+    /// The `band_width` is typically about twice the particle spacing, i.e., four times the particle radius.
+    pub fn new(fluids: &[Fluid<N>], band_width: N) -> Self {
+        assert!(
+            band_width > N::zero(),
+            "The band width of a fluid level set must be strictly positive."
+        );
+
+        let mut positions = Vec::new();
+        let mut radii = Vec::new();
+        let mut hgrid = HGrid::new(band_width);
+
+        for fluid in fluids {
+            for position in &fluid.positions {
+                hgrid.insert(position, positions.len());
+                positions.push(*position);
+                radii.push(fluid.particle_radius());
+            }
+        }
+
+        Self {
+            band_width,
+            positions,
+            radii,
+            hgrid,
+        }
+    }
+
+    /// Builds the level set of the volume occupied by the given fluid.
+    pub fn from_fluid(fluid: &Fluid<N>, band_width: N) -> Self {
+        Self::new(std::slice::from_ref(fluid), band_width)
+    }
+
+    /// The width of the band around the surface where the signed distances are computed.
+    pub fn band_width(&self) -> N {
+        self.band_width
+    }
+
+    /// The signed distance from the given point to the surface of the fluids.
+    ///
+    /// This is negative inside of the fluids, and is equal to `self.band_width()` for points too far from the fluids.
+    pub fn distance(&self, point: &Point<N>) -> N {
+        let mut total_weight = N::zero();
+        let mut weighted_position = Vector::zeros();
+        let mut weighted_radius = N::zero();
+
+        for (_, particles) in self
+            .hgrid
+            .neighbor_cells(&self.hgrid.key(point), self.band_width)
+        {
+            for i in particles {
+                let x_i = self.positions[*i] - point;
+                let s2 = x_i.norm_squared() / (self.band_width * self.band_width);
+
+                if s2 < N::one() {
+                    let weight = (N::one() - s2).powi(3);
+                    total_weight += weight;
+                    // NOTE: the positions are relative to `point` to limit rounding errors.
+                    weighted_position += x_i * weight;
+                    weighted_radius += self.radii[*i] * weight;
+                }
+            }
+        }
+
+        if total_weight.is_zero() {
+            return self.band_width;
+        }
+
+        let distance = (weighted_position / total_weight).norm() - weighted_radius / total_weight;
+        distance.max(-self.band_width).min(self.band_width)
+    }
+
+    /// Returns `true` if the given point is inside of the fluids.
+    pub fn contains_point(&self, point: &Point<N>) -> bool {
+        self.distance(point) < N::zero()
+    }
+
+    /// Samples the signed distance on a regular grid covering the box `[mins, maxs]`.
+    ///
+    /// The grid points are spaced by `cell_width`, starting from `mins`. The last grid point along each axis
+    /// is the first one greater than or equal to the corresponding coordinate of `maxs`.
+    pub fn rasterize(
+        &self,
+        mins: &Point<N>,
+        maxs: &Point<N>,
+        cell_width: N,
+    ) -> SignedDistanceGrid<N> {
+        assert!(
+            cell_width > N::zero(),
+            "The cell width of a signed distance grid must be strictly positive."
+        );
+
+        let mut dimensions = [0; DIM];
+
+        for k in 0..DIM {
+            let extent = ((maxs[k] - mins[k]) / cell_width).ceil().max(N::zero());
+            dimensions[k] = na::try_convert::<N, f64>(extent).unwrap() as usize + 1;
+        }
+
+        let mut grid = SignedDistanceGrid {
+            origin: *mins,
+            cell_width,
+            dimensions,
+            values: Vec::new(),
+        };
+
+        let num_points = dimensions.iter().product();
+        let mut values = vec![N::zero(); num_points];
+
+        par_iter_mut!(values)
+            .enumerate()
+            .for_each(|(index, value)| {
+                let mut coords = [0; DIM];
+                let mut rest = index;
+
+                for (c, dim) in coords.iter_mut().zip(dimensions.iter()) {
+                    *c = rest % dim;
+                    rest /= dim;
+                }
+
+                *value = self.distance(&grid.grid_point(coords));
+            });
+
+        grid.values = values;
+        grid
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{FluidLevelSet, SignedDistanceGrid};
+    use crate::math::{Point, Vector, DIM};
+    use crate::object::Fluid;
+
+    // A grid where the signed distance is the sum of the coordinates of each grid point.
+    fn linear_grid() -> SignedDistanceGrid<f64> {
+        let mut grid = SignedDistanceGrid {
+            origin: Point::from(Vector::repeat(1.0)),
+            cell_width: 0.5,
+            dimensions: [3; DIM],
+            values: vec![0.0; 3usize.pow(DIM as u32)],
+        };
+
+        for i in 0..grid.values.len() {
+            let mut coords = [0; DIM];
+            let mut rest = i;
+
+            for c in coords.iter_mut() {
+                *c = rest % 3;
+                rest /= 3;
+            }
+
+            assert_eq!(grid.index(coords), i);
+            grid.values[i] = grid.grid_point(coords).coords.sum();
+        }
+
+        grid
+    }
+
+    #[test]
+    fn sample_interpolates_linearly() {
+        let grid = linear_grid();
+
+        for point in &[
+            Point::from(Vector::repeat(1.0)),
+            Point::from(Vector::repeat(1.3)),
+            Point::from(Vector::repeat(2.0)),
+            Point::from(Vector::x() * 0.7 + Vector::repeat(1.2)),
+        ] {
+            let value = grid.sample(point).unwrap();
+            assert!((value - point.coords.sum()).abs() < 1.0e-12);
+        }
+
+        assert_eq!(grid.sample(&Point::from(Vector::repeat(0.9))), None);
+        assert_eq!(grid.sample(&Point::from(Vector::repeat(2.1))), None);
+    }
+
+    #[test]
+    fn sample_empty_grid() {
+        let mut grid = linear_grid();
+        grid.dimensions[0] = 0;
+        assert_eq!(grid.sample(&Point::from(Vector::repeat(1.0))), None);
+    }
+
+    #[test]
+    fn signed_distance_to_particles() {
+        let fluid = Fluid::<f64>::new(vec![Point::origin()], 0.1, 1000.0);
+        let level_set = FluidLevelSet::from_fluid(&fluid, 0.4);
+
+        assert!((level_set.distance(&Point::origin()) + 0.1).abs() < 1.0e-12);
+        assert!((level_set.distance(&Point::from(Vector::x() * 0.3)) - 0.2).abs() < 1.0e-12);
+        assert_eq!(level_set.distance(&Point::from(Vector::x() * 0.5)), 0.4);
+        assert!(level_set.contains_point(&Point::from(Vector::x() * 0.05)));
+
+        let grid = level_set.rasterize(
+            &Point::from(Vector::repeat(-0.2)),
+            &Point::from(Vector::repeat(0.2)),
+            0.1,
+        );
+        assert_eq!(grid.dimensions, [5; DIM]);
+        assert!((grid.sample(&Point::origin()).unwrap() + 0.1).abs() < 1.0e-12);
+    }
+}
@@ -0,0 +1,10 @@
+//! Reconstruction of the surface of the fluids as a mesh or a level set, e.g., for rendering.
+
+pub use self::fluid_level_set::{FluidLevelSet, SignedDistanceGrid};
+pub use self::surface_mesh::SurfaceMesh;
+pub use self::surface_reconstruction::SurfaceReconstruction;
+
+mod fluid_level_set;
+mod marching_cubes;
+mod surface_mesh;
+mod surface_reconstruction;
@@ -0,0 +1,189 @@
+use std::io::{self, Write};
+
+use na::RealField;
+
+use crate::math::{Point, DIM};
+
+/// A mesh approximating the surface of a fluid.
+///
+/// This is a triangle mesh in 3D, and a set of segments in 2D.
+#[derive(Clone, Debug)]
+pub struct SurfaceMesh<N: RealField> {
+    /// The vertices of the mesh.
+    pub vertices: Vec<Point<N>>,
+    /// The triangles (in 3D) or segments (in 2D) of the mesh, given as indices of `self.vertices`.
+    ///
+    /// The triangles are oriented counterclockwise when seen from outside of the fluid.
+    pub elements: Vec<[usize; DIM]>,
+}
+
+impl<N: RealField> SurfaceMesh<N> {
+    /// Initializes an empty mesh.
+    pub fn new() -> Self {
+        Self {
+            vertices: Vec::new(),
+            elements: Vec::new(),
+        }
+    }
+
+    /// Returns `true` if this mesh does not contain any element.
+    pub fn is_empty(&self) -> bool {
+        self.elements.is_empty()
+    }
+
+    // The coordinates of the given vertex, padded with zeros to three dimensions.
+    fn vertex_coordinates(vertex: &Point<N>) -> [f64; 3] {
+        let mut coords = [0.0; 3];
+
+        for (c, e) in coords.iter_mut().zip(vertex.coords.iter()) {
+            *c = na::try_convert(*e).unwrap();
+        }
+
+        coords
+    }
+
+    /// Writes this mesh in the Wavefront OBJ format.
+    ///
+    /// In 2D, the segments are written as lines, and all the vertices have a zero `z` coordinate.
+    pub fn write_obj(&self, out: &mut impl Write) -> io::Result<()> {
+        for vertex in &self.vertices {
+            let [x, y, z] = Self::vertex_coordinates(vertex);
+            writeln!(out, "v {} {} {}", x, y, z)?;
+        }
+
+        let prefix = if DIM == 2 { "l" } else { "f" };
+
+        for element in &self.elements {
+            write!(out, "{}", prefix)?;
+
+            // NOTE: OBJ indices start at 1.
+            for id in element {
+                write!(out, " {}", id + 1)?;
+            }
+
+            writeln!(out)?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes this mesh in the ASCII PLY format.
+    ///
+    /// In 2D, the segments are written as edges, and all the vertices have a zero `z` coordinate.
+    pub fn write_ply(&self, out: &mut impl Write) -> io::Result<()> {
+        writeln!(out, "ply")?;
+        writeln!(out, "format ascii 1.0")?;
+        writeln!(out, "element vertex {}", self.vertices.len())?;
+        writeln!(out, "property double x")?;
+        writeln!(out, "property double y")?;
+        writeln!(out, "property double z")?;
+
+        if DIM == 2 {
+            writeln!(out, "element edge {}", self.elements.len())?;
+            writeln!(out, "property int vertex1")?;
+            writeln!(out, "property int vertex2")?;
+        } else {
+            writeln!(out, "element face {}", self.elements.len())?;
+            writeln!(out, "property list uchar int vertex_indices")?;
+        }
+
+        writeln!(out, "end_header")?;
+
+        for vertex in &self.vertices {
+            let [x, y, z] = Self::vertex_coordinates(vertex);
+            writeln!(out, "{} {} {}", x, y, z)?;
+        }
+
+        for element in &self.elements {
+            let ids: Vec<_> = element.iter().map(|id| id.to_string()).collect();
+
+            if DIM == 2 {
+                writeln!(out, "{}", ids.join(" "))?;
+            } else {
+                writeln!(out, "{} {}", DIM, ids.join(" "))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<N: RealField> Default for SurfaceMesh<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::SurfaceMesh;
+    use crate::math::Point;
+
+    #[cfg(feature = "dim2")]
+    fn segment() -> SurfaceMesh<f64> {
+        SurfaceMesh {
+            vertices: vec![Point::new(1.0, 2.0), Point::new(3.0, 4.5)],
+            elements: vec![[0, 1]],
+        }
+    }
+
+    #[cfg(feature = "dim3")]
+    fn triangle() -> SurfaceMesh<f64> {
+        SurfaceMesh {
+            vertices: vec![
+                Point::new(1.0, 2.0, 3.0),
+                Point::new(4.0, 5.0, 6.0),
+                Point::new(7.0, 8.0, 9.5),
+            ],
+            elements: vec![[0, 1, 2]],
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "dim2")]
+    fn write_obj_2d() {
+        let mut out = Vec::new();
+        segment().write_obj(&mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "v 1 2 0\nv 3 4.5 0\nl 1 2\n"
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "dim3")]
+    fn write_obj_3d() {
+        let mut out = Vec::new();
+        triangle().write_obj(&mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "v 1 2 3\nv 4 5 6\nv 7 8 9.5\nf 1 2 3\n"
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "dim2")]
+    fn write_ply_2d() {
+        let mut out = Vec::new();
+        segment().write_ply(&mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "ply\nformat ascii 1.0\nelement vertex 2\nproperty double x\nproperty double y\n\
+             property double z\nelement edge 1\nproperty int vertex1\nproperty int vertex2\n\
+             end_header\n1 2 0\n3 4.5 0\n0 1\n"
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "dim3")]
+    fn write_ply_3d() {
+        let mut out = Vec::new();
+        triangle().write_ply(&mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "ply\nformat ascii 1.0\nelement vertex 3\nproperty double x\nproperty double y\n\
+             property double z\nelement face 1\nproperty list uchar int vertex_indices\n\
+             end_header\n1 2 3\n4 5 6\n7 8 9.5\n3 0 1 2\n"
+        );
+    }
+}
@@ -0,0 +1,370 @@
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+use std::collections::{HashMap, HashSet};
+use std::marker::PhantomData;
+
+use na::{self, RealField};
+
+use crate::geometry::HGrid;
+use crate::kernel::{CubicSplineKernel, Kernel};
+use crate::math::{Matrix, Point, DIM};
+use crate::object::Fluid;
+use crate::reconstruction::marching_cubes::{self, CORNERS, EDGES};
+use crate::reconstruction::SurfaceMesh;
+
+// The edge of the sampling grid between two adjacent grid points.
+type GridEdge = (Point<i64>, Point<i64>);
+
+fn grid_edge(a: Point<i64>, b: Point<i64>) -> GridEdge {
+    if a.coords.iter().lt(b.coords.iter()) {
+        (a, b)
+    } else {
+        (b, a)
+    }
+}
+
+fn offset_key(key: &Point<i64>, offset: &[i64]) -> Point<i64> {
+    let mut result = *key;
+
+    for (r, o) in result.coords.iter_mut().zip(offset.iter()) {
+        *r += *o;
+    }
+
+    result
+}
+
+// Calls `f` on each grid point with integer coordinates in the box `[mins, maxs]`.
+fn for_each_key_in_range(mins: &Point<i64>, maxs: &Point<i64>, mut f: impl FnMut(Point<i64>)) {
+    if mins
+        .coords
+        .iter()
+        .zip(maxs.coords.iter())
+        .any(|(a, b)| a > b)
+    {
+        return;
+    }
+
+    let mut key = *mins;
+
+    loop {
+        f(key);
+
+        let mut k = 0;
+
+        while k < DIM {
+            if key[k] < maxs[k] {
+                key[k] += 1;
+                break;
+            }
+
+            key[k] = mins[k];
+            k += 1;
+        }
+
+        if k == DIM {
+            return;
+        }
+    }
+}
+
+/// Reconstruction of the surface of a fluid as a mesh, using marching cubes in 3D and marching squares in 2D.
+///
+/// The surface is the iso-surface of the scalar field equal to the sum of the volumes of the particles
+/// weighted by the kernel `KernelDensity`. This field is close to the ratio between the particle volumes
+/// and the volume they occupy inside of the fluid, and is zero far from the fluid. It is sampled on a sparse
+/// grid covering only the neighborhood of the particles, so the extracted mesh is always a closed manifold.
+pub struct SurfaceReconstruction<N: RealField, KernelDensity: Kernel = CubicSplineKernel> {
+    /// The value of the scalar field at the reconstructed surface.
+    ///
+    /// This must be strictly positive.
+    pub iso_level: N,
+    /// The width of the cells of the sampling grid, relative to the particle radius.
+    pub cell_width_factor: N,
+    /// The radius of the kernel used to compute the scalar field, relative to the particle radius.
+    pub kernel_radius_factor: N,
+    phantom: PhantomData<KernelDensity>,
+}
+
+impl<N, KernelDensity> SurfaceReconstruction<N, KernelDensity>
+where
+    N: RealField,
+    KernelDensity: Kernel,
+{
+    /// Initializes a surface reconstruction with default parameters.
+    pub fn new() -> Self {
+        Self {
+            iso_level: na::convert(0.3),
+            cell_width_factor: N::one(),
+            kernel_radius_factor: na::convert(4.0),
+            phantom: PhantomData,
+        }
+    }
+
+    /// Reconstructs the surface of the given fluid.
+    pub fn reconstruct(&self, fluid: &Fluid<N>) -> SurfaceMesh<N> {
+        self.reconstruct_particles(fluid.particle_radius(), &fluid.positions, &fluid.volumes)
+    }
+
+    /// Reconstructs the surface of the given set of particles, given their position and volume.
+    pub fn reconstruct_particles(
+        &self,
+        particle_radius: N,
+        positions: &[Point<N>],
+        volumes: &[N],
+    ) -> SurfaceMesh<N> {
+        assert_eq!(positions.len(), volumes.len());
+
+        let kernel_radius = particle_radius * self.kernel_radius_factor;
+        let support_radii = vec![kernel_radius; positions.len()];
+
+        self.reconstruct_field(particle_radius, positions, &support_radii, |j, point| {
+            volumes[j] * KernelDensity::points_apply(point, &positions[j], kernel_radius)
+        })
+    }
+
+    /// Reconstructs the surface of the given fluid, with anisotropic particles.
+    ///
+    /// The `centers` and ellipsoid `transforms` of the particles are typically computed by `Yu2013Anisotropy`.
+    pub fn reconstruct_anisotropic(
+        &self,
+        fluid: &Fluid<N>,
+        centers: &[Point<N>],
+        transforms: &[Matrix<N>],
+    ) -> SurfaceMesh<N> {
+        self.reconstruct_anisotropic_particles(
+            fluid.particle_radius(),
+            centers,
+            &fluid.volumes,
+            transforms,
+        )
+    }
+
+    /// Reconstructs the surface of the given set of anisotropic particles, given their center, volume, and
+    /// ellipsoid transform.
+    ///
+    /// The kernel of each particle is stretched by its ellipsoid transform, and normalized by its determinant.
+    pub fn reconstruct_anisotropic_particles(
+        &self,
+        particle_radius: N,
+        centers: &[Point<N>],
+        volumes: &[N],
+        transforms: &[Matrix<N>],
+    ) -> SurfaceMesh<N> {
+        assert_eq!(centers.len(), volumes.len());
+        assert_eq!(centers.len(), transforms.len());
+
+        let kernel_radius = particle_radius * self.kernel_radius_factor;
+        let inv_transforms: Vec<_> = transforms
+            .iter()
+            .map(|t| {
+                t.try_inverse()
+                    .expect("The ellipsoid transforms must be invertible.")
+            })
+            .collect();
+        let inv_determinants: Vec<_> = inv_transforms
+            .iter()
+            .map(|t| t.determinant().abs())
+            .collect();
+        // NOTE: the Frobenius norm of a transform is an upper bound of its largest stretch factor.
+        let support_radii: Vec<_> = transforms
+            .iter()
+            .map(|t| kernel_radius * t.norm())
+            .collect();
+
+        self.reconstruct_field(particle_radius, centers, &support_radii, |j, point| {
+            let v = inv_transforms[j] * (point - centers[j]);
+            volumes[j] * inv_determinants[j] * KernelDensity::apply(v, kernel_radius)
+        })
+    }
+
+    // Extracts the iso-surface of the field equal to the sum of `contribution(j, point)` for all the
+    // particles `j` closer than `support_radii[j]` to the `point`.
+    fn reconstruct_field(
+        &self,
+        particle_radius: N,
+        positions: &[Point<N>],
+        support_radii: &[N],
+        contribution: impl Fn(usize, &Point<N>) -> N + Sync,
+    ) -> SurfaceMesh<N> {
+        assert!(
+            self.iso_level > N::zero(),
+            "The iso-level of the surface reconstruction must be strictly positive."
+        );
+
+        let max_support_radius = support_radii.iter().fold(N::zero(), |acc, r| acc.max(*r));
+
+        if max_support_radius.is_zero() {
+            return SurfaceMesh::new();
+        }
+
+        let cell_width = particle_radius * self.cell_width_factor;
+        let iso_level = self.iso_level;
+
+        let mut hgrid = HGrid::new(max_support_radius);
+
+        for (i, position) in positions.iter().enumerate() {
+            hgrid.insert(position, i);
+        }
+
+        let grid_point = |key: &Point<i64>| -> Point<N> {
+            Point::from(
+                key.coords
+                    .map(|e| na::convert::<f64, N>(e as f64) * cell_width),
+            )
+        };
+
+        // Collect the grid points close enough to the particles to have a non-zero field value.
+        let mut key_set = HashSet::new();
+
+        for (position, support_radius) in positions.iter().zip(support_radii.iter()) {
+            let support_radius = *support_radius;
+            let mins = position.coords.map(|e| {
+                na::try_convert::<N, f64>(((e - support_radius) / cell_width).ceil()).unwrap()
+                    as i64
+            });
+            let maxs = position.coords.map(|e| {
+                na::try_convert::<N, f64>(((e + support_radius) / cell_width).floor()).unwrap()
+                    as i64
+            });
+
+            for_each_key_in_range(&Point::from(mins), &Point::from(maxs), |key| {
+                if na::distance_squared(&grid_point(&key), position)
+                    < support_radius * support_radius
+                {
+                    let _ = key_set.insert(key);
+                }
+            });
+        }
+
+        let mut keys: Vec<_> = key_set.into_iter().collect();
+        keys.sort_by(|a, b| a.coords.iter().cmp(b.coords.iter()));
+
+        let values: Vec<N> = par_iter!(keys)
+            .map(|key| {
+                let point = grid_point(key);
+                let mut value = N::zero();
+
+                for (_, particles) in hgrid.neighbor_cells(&hgrid.key(&point), max_support_radius) {
+                    for j in particles {
+                        if na::distance_squared(&point, &positions[*j])
+                            < support_radii[*j] * support_radii[*j]
+                        {
+                            value += contribution(*j, &point);
+                        }
+                    }
+                }
+
+                value
+            })
+            .collect();
+
+        let key_ids: HashMap<_, _> = keys.iter().enumerate().map(|(i, key)| (*key, i)).collect();
+        // NOTE: the grid points that were not sampled are too far from the particles, so their value is zero.
+        let value = |key: &Point<i64>| key_ids.get(key).map(|i| values[*i]).unwrap_or_else(N::zero);
+
+        // Collect the cells with at least one corner inside of the surface.
+        let mut cell_set = HashSet::new();
+
+        for (key, value) in keys.iter().zip(values.iter()) {
+            if *value > iso_level {
+                for corner in CORNERS.iter() {
+                    let _ = cell_set.insert(offset_key(key, &corner.map(|e| -e)));
+                }
+            }
+        }
+
+        let mut cells: Vec<_> = cell_set.into_iter().collect();
+        cells.sort_by(|a, b| a.coords.iter().cmp(b.coords.iter()));
+
+        let cell_elements: Vec<Vec<[GridEdge; DIM]>> = par_iter!(cells)
+            .map(|cell| {
+                let corners: Vec<_> = CORNERS.iter().map(|c| offset_key(cell, c)).collect();
+                let configuration = corners
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, corner)| value(corner) > iso_level)
+                    .fold(0, |acc, (k, _)| acc | (1 << k));
+
+                marching_cubes::cell_elements(configuration)
+                    .map(|element| {
+                        let mut result = [grid_edge(*cell, *cell); DIM];
+
+                        for (r, e) in result.iter_mut().zip(element.iter()) {
+                            let [a, b] = EDGES[*e];
+                            *r = grid_edge(corners[a], corners[b]);
+                        }
+
+                        result
+                    })
+                    .collect()
+            })
+            .collect();
+
+        // Create one vertex per grid edge crossed by the surface, so that adjacent cells share their vertices.
+        let mut mesh = SurfaceMesh::new();
+        let mut vertex_ids = HashMap::new();
+
+        for elements in cell_elements {
+            for element in elements {
+                let mut ids = [0; DIM];
+
+                for (id, edge) in ids.iter_mut().zip(element.iter()) {
+                    *id = *vertex_ids.entry(*edge).or_insert_with(|| {
+                        let (a, b) = edge;
+                        let (value_a, value_b) = (value(a), value(b));
+                        let t = (iso_level - value_a) / (value_b - value_a);
+                        let (point_a, point_b) = (grid_point(a), grid_point(b));
+                        mesh.vertices.push(point_a + (point_b - point_a) * t);
+                        mesh.vertices.len() - 1
+                    });
+                }
+
+                mesh.elements.push(ids);
+            }
+        }
+
+        mesh
+    }
+}
+
+impl<N, KernelDensity> Default for SurfaceReconstruction<N, KernelDensity>
+where
+    N: RealField,
+    KernelDensity: Kernel,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::SurfaceReconstruction;
+    use crate::math::{Point, Vector};
+
+    #[test]
+    fn single_particle_surface() {
+        let reconstruction = SurfaceReconstruction::<f64>::new();
+        let center = Point::from(Vector::repeat(0.3));
+        let mesh = reconstruction.reconstruct_particles(0.1, &[center], &[0.1]);
+        assert!(!mesh.is_empty());
+
+        // The surface surrounds the particle, within its kernel radius.
+        for vertex in &mesh.vertices {
+            let distance = na::distance(vertex, &center);
+            assert!(distance > 0.0 && distance < 0.4);
+        }
+
+        // Every vertex is shared by adjacent elements.
+        let mut vertex_uses = vec![0; mesh.vertices.len()];
+
+        for element in &mesh.elements {
+            for id in element {
+                vertex_uses[*id] += 1;
+            }
+        }
+
+        assert!(vertex_uses.iter().all(|uses| *uses >= 2));
+    }
+}
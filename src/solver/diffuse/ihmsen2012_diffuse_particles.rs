@@ -0,0 +1,511 @@
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+use na::{self, RealField, Unit};
+
+use crate::analysis::FreeSurface;
+use crate::geometry::{ContactManager, HGrid, HGridEntry};
+use crate::math::{Point, Vector, DIM};
+use crate::object::{Boundary, DiffuseParticleKind, DiffuseParticles, Fluid};
+use crate::TimestepManager;
+
+// The radially symmetric weighting function used for the potentials.
+fn weight<N: RealField>(distance: N, kernel_radius: N) -> N {
+    (N::one() - distance / kernel_radius).max(N::zero())
+}
+
+fn kind_from_neighbors(
+    num_neighbors: usize,
+    spray_max_neighbors: usize,
+    bubble_min_neighbors: usize,
+) -> DiffuseParticleKind {
+    if num_neighbors < spray_max_neighbors {
+        DiffuseParticleKind::Spray
+    } else if num_neighbors > bubble_min_neighbors {
+        DiffuseParticleKind::Bubble
+    } else {
+        DiffuseParticleKind::Foam
+    }
+}
+
+// Maps `value` to [0, 1] depending on its location in the given range.
+fn clamped_potential<N: RealField>(value: N, range: (N, N)) -> N {
+    if range.1 <= range.0 {
+        return N::zero();
+    }
+
+    (value.min(range.1) - value.min(range.0)) / (range.1 - range.0)
+}
+
+/// Diffuse particles model introduced by Ihmsen et al. 2012.
+///
+/// Spray, foam, and bubble particles are emitted from the fluid particles that are likely to
+/// trap air or to be at a wave crest, provided they have enough kinetic energy. Only the particles at
+/// the free surface, and their neighbors at the free surface, are considered for wave crests. Diffuse particles
+/// are then classified according to their number of fluid neighbors, and advected:
+/// - ballistically for spray particles,
+/// - along the fluid velocity for foam particles,
+/// - with buoyancy and drag for bubbles particles.
+///
+/// Each kind of diffuse particle has its own lifetime. When the kind of a particle changes, its
+/// remaining lifetime is rescaled to the lifetime of its new kind so the elapsed fraction of its
+/// lifetime is preserved.
+pub struct Ihmsen2012DiffuseParticles<N: RealField> {
+    /// The range of the trapped air potential mapped to emission probabilities in [0, 1].
+    pub trapped_air_range: (N, N),
+    /// The range of the wave crest potential mapped to emission probabilities in [0, 1].
+    pub wave_crest_range: (N, N),
+    /// The range of the kinetic energy mapped to emission probabilities in [0, 1].
+    pub kinetic_energy_range: (N, N),
+    /// The maximum number of diffuse particles emitted per second per fluid particle due to trapped air.
+    pub trapped_air_rate: N,
+    /// The maximum number of diffuse particles emitted per second per fluid particle at wave crests.
+    pub wave_crest_rate: N,
+    /// The lifetime of spray particles.
+    pub spray_lifetime: N,
+    /// The lifetime of foam particles.
+    pub foam_lifetime: N,
+    /// The lifetime of bubble particles.
+    pub bubble_lifetime: N,
+    /// Diffuse particles with less fluid neighbors than this are spray particles.
+    pub spray_max_neighbors: usize,
+    /// Diffuse particles with more fluid neighbors than this are bubble particles.
+    pub bubble_min_neighbors: usize,
+    /// The buoyancy coefficient of bubble particles.
+    pub buoyancy_coefficient: N,
+    /// The drag coefficient of bubble particles, in [0, 1].
+    pub drag_coefficient: N,
+    /// The maximum number of diffuse particles.
+    ///
+    /// No diffuse particle is emitted if this number is reached.
+    pub max_particles: usize,
+    particles: DiffuseParticles<N>,
+    surface: FreeSurface<N>,
+    emission_counts: Vec<Vec<(N, usize)>>,
+    rng_state: u64,
+}
+
+impl<N: RealField> Ihmsen2012DiffuseParticles<N> {
+    /// Initializes a diffuse particles model with default parameters.
+    pub fn new() -> Self {
+        Self {
+            trapped_air_range: (na::convert(5.0), na::convert(20.0)),
+            wave_crest_range: (na::convert(2.0), na::convert(8.0)),
+            kinetic_energy_range: (na::convert(5.0), na::convert(50.0)),
+            trapped_air_rate: na::convert(100.0),
+            wave_crest_rate: na::convert(100.0),
+            spray_lifetime: na::convert(2.0),
+            foam_lifetime: na::convert(5.0),
+            bubble_lifetime: na::convert(2.0),
+            spray_max_neighbors: if DIM == 2 { 3 } else { 6 },
+            bubble_min_neighbors: if DIM == 2 { 8 } else { 20 },
+            buoyancy_coefficient: na::convert(2.0),
+            drag_coefficient: na::convert(0.5),
+            max_particles: 100_000,
+            particles: DiffuseParticles::new(),
+            surface: FreeSurface::new(),
+            emission_counts: Vec::new(),
+            rng_state: 0x9E37_79B9_7F4A_7C15,
+        }
+    }
+
+    /// The diffuse particles generated by this model.
+    pub fn particles(&self) -> &DiffuseParticles<N> {
+        &self.particles
+    }
+
+    /// The mutable diffuse particles generated by this model.
+    pub fn particles_mut(&mut self) -> &mut DiffuseParticles<N> {
+        &mut self.particles
+    }
+
+    /// Sets the seed of the pseudo-random number generator used for emitting diffuse particles.
+    pub fn set_seed(&mut self, seed: u64) {
+        // NOTE: the xorshift state must not be zero.
+        self.rng_state = seed.max(1);
+    }
+
+    // A pseudo-random number in [0, 1) generated with xorshift64*.
+    fn random(&mut self) -> N {
+        self.rng_state ^= self.rng_state >> 12;
+        self.rng_state ^= self.rng_state << 25;
+        self.rng_state ^= self.rng_state >> 27;
+        let bits = self.rng_state.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 11;
+        na::convert(bits as f64 / (1u64 << 53) as f64)
+    }
+
+    /// The lifetime of the diffuse particles of the given kind.
+    pub fn lifetime(&self, kind: DiffuseParticleKind) -> N {
+        match kind {
+            DiffuseParticleKind::Spray => self.spray_lifetime,
+            DiffuseParticleKind::Foam => self.foam_lifetime,
+            DiffuseParticleKind::Bubble => self.bubble_lifetime,
+        }
+    }
+
+    /// Advects the existing diffuse particles and emits new ones from the fluids.
+    pub(crate) fn update(
+        &mut self,
+        timestep: &TimestepManager<N>,
+        kernel_radius: N,
+        gravity: &Vector<N>,
+        contact_manager: &ContactManager<N>,
+        hgrid: &HGrid<N, HGridEntry>,
+        fluids: &[Fluid<N>],
+        boundaries: &[Boundary<N>],
+        densities: &[Vec<N>],
+    ) {
+        self.advect_particles(timestep, kernel_radius, gravity, hgrid, fluids);
+        self.surface.compute(
+            kernel_radius,
+            contact_manager,
+            fluids,
+            boundaries,
+            densities,
+        );
+        self.compute_emission_counts(timestep, kernel_radius, contact_manager, fluids);
+        self.emit_particles(timestep, fluids);
+    }
+
+    fn advect_particles(
+        &mut self,
+        timestep: &TimestepManager<N>,
+        kernel_radius: N,
+        gravity: &Vector<N>,
+        hgrid: &HGrid<N, HGridEntry>,
+        fluids: &[Fluid<N>],
+    ) {
+        let dt = timestep.dt();
+        let buoyancy_coefficient = self.buoyancy_coefficient;
+        let drag_coefficient = self.drag_coefficient;
+        let (spray_lifetime, foam_lifetime, bubble_lifetime) = (
+            self.spray_lifetime,
+            self.foam_lifetime,
+            self.bubble_lifetime,
+        );
+        let kind_lifetime = |kind| match kind {
+            DiffuseParticleKind::Spray => spray_lifetime,
+            DiffuseParticleKind::Foam => foam_lifetime,
+            DiffuseParticleKind::Bubble => bubble_lifetime,
+        };
+        let spray_max_neighbors = self.spray_max_neighbors;
+        let bubble_min_neighbors = self.bubble_min_neighbors;
+        let particles = &mut self.particles;
+
+        par_iter_mut!(particles.positions)
+            .zip(par_iter_mut!(particles.velocities))
+            .zip(par_iter_mut!(particles.kinds))
+            .zip(par_iter_mut!(particles.lifetimes))
+            .for_each(|(((position, velocity), kind), lifetime)| {
+                let mut num_neighbors = 0;
+                let mut fluid_velocity = Vector::zeros();
+                let mut total_weight = N::zero();
+
+                for (_, entries) in hgrid.neighbor_cells(&hgrid.key(position), kernel_radius) {
+                    for entry in entries {
+                        if let HGridEntry::FluidParticle(fluid_id, particle_id) = entry {
+                            let fluid = &fluids[*fluid_id];
+                            let distance = na::distance(position, &fluid.positions[*particle_id]);
+
+                            if distance < kernel_radius {
+                                let w = weight(distance, kernel_radius);
+                                num_neighbors += 1;
+                                fluid_velocity += fluid.velocities[*particle_id] * w;
+                                total_weight += w;
+                            }
+                        }
+                    }
+                }
+
+                if !total_weight.is_zero() {
+                    fluid_velocity /= total_weight;
+                }
+
+                let new_kind =
+                    kind_from_neighbors(num_neighbors, spray_max_neighbors, bubble_min_neighbors);
+
+                match new_kind {
+                    DiffuseParticleKind::Spray => *velocity += gravity * dt,
+                    DiffuseParticleKind::Foam => *velocity = fluid_velocity,
+                    DiffuseParticleKind::Bubble => {
+                        *velocity += gravity * (-buoyancy_coefficient * dt)
+                            + (fluid_velocity - *velocity) * drag_coefficient
+                    }
+                }
+
+                *position += *velocity * dt;
+
+                if new_kind != *kind {
+                    let old_lifetime = kind_lifetime(*kind);
+
+                    if !old_lifetime.is_zero() {
+                        *lifetime *= kind_lifetime(new_kind) / old_lifetime;
+                    }
+
+                    *kind = new_kind;
+                }
+
+                *lifetime -= dt;
+            });
+
+        particles.remove_dead_particles();
+    }
+
+    fn compute_emission_counts(
+        &mut self,
+        timestep: &TimestepManager<N>,
+        kernel_radius: N,
+        contact_manager: &ContactManager<N>,
+        fluids: &[Fluid<N>],
+    ) {
+        let dt = timestep.dt();
+        let surface = &self.surface;
+        let trapped_air_range = self.trapped_air_range;
+        let wave_crest_range = self.wave_crest_range;
+        let kinetic_energy_range = self.kinetic_energy_range;
+        let trapped_air_rate = self.trapped_air_rate;
+        let wave_crest_rate = self.wave_crest_rate;
+        let wave_crest_threshold: N = na::convert(0.6);
+        self.emission_counts.resize(fluids.len(), Vec::new());
+
+        for (fluid_id, (fluid, emission_counts)) in fluids
+            .iter()
+            .zip(self.emission_counts.iter_mut())
+            .enumerate()
+        {
+            emission_counts.resize(fluid.num_particles(), (N::zero(), 0));
+            let fluid_fluid_contacts = &contact_manager.fluid_fluid_contacts[fluid_id];
+
+            par_iter_mut!(emission_counts)
+                .enumerate()
+                .for_each(|(i, emission_count)| {
+                    let vi = fluid.velocities[i];
+                    let ni = surface.normals(fluid_id)[i];
+                    // The normals of the particles inside of the fluid are meaningless, so
+                    // only the surface particles and their surface neighbors form wave crests.
+                    let at_surface = surface.surface_particles(fluid_id)[i];
+                    let kinetic_energy =
+                        fluid.particle_mass(i) * vi.norm_squared() * na::convert(0.5);
+                    let mut trapped_air = N::zero();
+                    let mut wave_crest = N::zero();
+                    let mut num_neighbors = 0;
+
+                    for c in fluid_fluid_contacts
+                        .particle_contacts(i)
+                        .read()
+                        .unwrap()
+                        .iter()
+                    {
+                        if c.is_same_particle_contact() {
+                            continue;
+                        }
+
+                        num_neighbors += 1;
+                        let fluid_j = &fluids[c.j_model];
+                        let x_ij = fluid.positions[c.i] - fluid_j.positions[c.j];
+                        let v_ij = vi - fluid_j.velocities[c.j];
+                        let w = weight(x_ij.norm(), kernel_radius);
+
+                        if let (Some(dir_x), Some((dir_v, norm_v))) = (
+                            Unit::try_new(x_ij, N::default_epsilon()),
+                            Unit::try_new_and_get(v_ij, N::default_epsilon()),
+                        ) {
+                            trapped_air += norm_v * (N::one() - dir_v.dot(&dir_x)) * w;
+                        }
+
+                        // Only neighbors behind the particle (relative to its normal) contribute to the curvature.
+                        if at_surface
+                            && surface.surface_particles(c.j_model)[c.j]
+                            && x_ij.dot(&ni) > N::zero()
+                        {
+                            let nj = surface.normals(c.j_model)[c.j];
+                            wave_crest += (N::one() - ni.dot(&nj)) * w;
+                        }
+                    }
+
+                    // Only particles moving in the direction of their normal are at a wave crest.
+                    let moves_along_normal = vi
+                        .try_normalize(N::default_epsilon())
+                        .map(|dir| dir.dot(&ni) >= wave_crest_threshold)
+                        .unwrap_or(false);
+
+                    if !at_surface || !moves_along_normal {
+                        wave_crest = N::zero();
+                    }
+
+                    let count = clamped_potential(kinetic_energy, kinetic_energy_range)
+                        * (trapped_air_rate * clamped_potential(trapped_air, trapped_air_range)
+                            + wave_crest_rate * clamped_potential(wave_crest, wave_crest_range))
+                        * dt;
+
+                    *emission_count = (count, num_neighbors);
+                })
+        }
+    }
+
+    fn emit_particles(&mut self, timestep: &TimestepManager<N>, fluids: &[Fluid<N>]) {
+        let dt = timestep.dt();
+
+        let all_emission_counts = std::mem::take(&mut self.emission_counts);
+
+        for (fluid, emission_counts) in fluids.iter().zip(all_emission_counts.iter()) {
+            let radius = fluid.particle_radius();
+
+            for (i, (count, num_neighbors)) in emission_counts.iter().enumerate() {
+                if count.is_zero() {
+                    continue;
+                }
+
+                let vi = fluid.velocities[i];
+                let dir = match vi.try_normalize(N::default_epsilon()) {
+                    Some(dir) => dir,
+                    None => continue,
+                };
+
+                // Emit `floor(count)` particles, plus one with a probability equal to the fractional part.
+                let mut num_emitted = count.floor();
+
+                if self.random() < count.fract() {
+                    num_emitted += N::one();
+                }
+
+                let basis = {
+                    let mut basis = Vec::new();
+                    Vector::orthonormal_subspace_basis(&[dir], |e| {
+                        basis.push(*e);
+                        true
+                    });
+                    basis
+                };
+                let kind = kind_from_neighbors(
+                    *num_neighbors,
+                    self.spray_max_neighbors,
+                    self.bubble_min_neighbors,
+                );
+                let lifetime = self.lifetime(kind);
+
+                while num_emitted >= N::one() && self.particles.len() < self.max_particles {
+                    num_emitted -= N::one();
+
+                    // Sample a point on a cylinder around the particle, oriented along its velocity.
+                    let mut offset = Vector::zeros();
+
+                    loop {
+                        offset.fill(N::zero());
+
+                        for e in &basis {
+                            offset += e * (self.random() * na::convert(2.0) - N::one());
+                        }
+
+                        if offset.norm_squared() <= N::one() {
+                            break;
+                        }
+                    }
+
+                    offset *= radius;
+                    let height = self.random() * vi.norm() * dt;
+                    let position: Point<N> = fluid.positions[i] + offset + dir * height;
+                    self.particles.push(position, vi + offset, kind, lifetime);
+                }
+            }
+        }
+
+        self.emission_counts = all_emission_counts;
+    }
+}
+
+impl<N: RealField> Default for Ihmsen2012DiffuseParticles<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Ihmsen2012DiffuseParticles;
+    use crate::geometry::HGrid;
+    use crate::math::{Point, Vector, DIM};
+    use crate::object::{DiffuseParticleKind, Fluid};
+    use crate::solver::DFSPHSolver;
+    use crate::{LiquidWorld, TimestepManager};
+
+    #[test]
+    fn lifetime_is_rescaled_when_kind_changes() {
+        let mut model = Ihmsen2012DiffuseParticles::<f64>::new();
+        model.foam_lifetime = 4.0;
+        model.spray_lifetime = 1.0;
+        model.particles_mut().push(
+            Point::origin(),
+            Vector::zeros(),
+            DiffuseParticleKind::Foam,
+            2.0,
+        );
+
+        let mut timestep = TimestepManager::new(0.1);
+        timestep.reset(0.125);
+        timestep.advance(&[]);
+
+        // Without any fluid neighbor, the foam particle becomes a spray particle
+        // while keeping half its lifetime.
+        let hgrid = HGrid::new(0.4);
+        model.advect_particles(&timestep, 0.4, &Vector::zeros(), &hgrid, &[]);
+        assert_eq!(model.particles().kinds[0], DiffuseParticleKind::Spray);
+        assert_eq!(model.particles().lifetimes[0], 0.375);
+
+        for _ in 0..3 {
+            model.advect_particles(&timestep, 0.4, &Vector::zeros(), &hgrid, &[]);
+        }
+
+        assert!(model.particles().is_empty());
+    }
+
+    #[test]
+    fn no_wave_crest_inside_of_the_fluid() {
+        let particle_radius = 0.025;
+        let n = 6usize;
+        let positions = (0..n.pow(DIM as u32))
+            .map(|mut id| {
+                let mut point = Point::origin();
+
+                for k in 0..DIM {
+                    point[k] = (id % n) as f64 * particle_radius * 2.0;
+                    id /= n;
+                }
+
+                point
+            })
+            .collect();
+        let mut world = LiquidWorld::new(DFSPHSolver::<f64>::new(), particle_radius, 2.0);
+        let _ = world.add_fluid(Fluid::new(positions, particle_radius, 1000.0));
+        world.step(0.01, &Vector::zeros());
+
+        // With a uniform velocity, no air is trapped so particles may only be emitted at wave crests.
+        world.fluids_mut().as_mut_slice()[0]
+            .velocities
+            .iter_mut()
+            .for_each(|v| *v = Vector::y() * 3.0);
+
+        let mut model = Ihmsen2012DiffuseParticles::new();
+        model.kinetic_energy_range = (0.0, 0.1);
+        let mut timestep = TimestepManager::new(particle_radius);
+        timestep.reset(0.01);
+        timestep.advance(&[]);
+
+        model.surface.compute_from_world(&world);
+        model.compute_emission_counts(
+            &timestep,
+            world.h(),
+            world.contact_manager(),
+            world.fluids().as_slice(),
+        );
+
+        let surface_particles = model.surface.surface_particles(0).to_vec();
+        assert!(surface_particles.iter().any(|s| !*s));
+
+        for (count, at_surface) in model.emission_counts[0].iter().zip(surface_particles) {
+            if !at_surface {
+                assert_eq!(count.0, 0.0);
+            }
+        }
+    }
+}
@@ -0,0 +1,3 @@
+pub use self::ihmsen2012_diffuse_particles::Ihmsen2012DiffuseParticles;
+
+mod ihmsen2012_diffuse_particles;
@@ -5,11 +5,14 @@ use rayon::prelude::*;
 
 use na::{self, RealField};
 
-use crate::geometry::{self, ParticlesContacts};
+use crate::counters::Counters;
+use crate::geometry::ParticlesContacts;
 use crate::kernel::{CubicSplineKernel, Kernel};
 use crate::math::{Matrix, Point, RotationMatrix, SpatialVector, Vector};
 use crate::object::{Boundary, Fluid};
-use crate::solver::NonPressureForce;
+use crate::solver::{
+    ElasticRestState, Fracture, NonPressureForce, ParticlePhase, PhaseTransition, Plasticity,
+};
 use crate::TimestepManager;
 
 fn elasticity_coefficients<N: RealField>(young_modulus: N, poisson_ratio: N) -> (N, N, N) {
@@ -38,6 +41,13 @@ fn sym_mat_mul_vec<N: RealField>(mat: &SpatialVector<N>, v: &Vector<N>) -> Vecto
 
 // https://cg.informatik.uni-freiburg.de/publications/2009_NP_corotatedSPH.pdf
 /// Elasticity based on the method from Becker et al. 2009.
+///
+/// If `self.phase_transition` is set, the particles can melt (i.e. stop being subject to elastic
+/// forces) and solidify again with a new rest configuration, depending on their temperature and stress.
+///
+/// If `self.plasticity` is set, the particles accumulate a plastic strain (i.e. their rest shape is updated)
+/// when their strain exceeds a yield strain, so the object can keep dents. If `self.fracture` is set, the rest
+/// contacts between particles stretched too much are removed, so the object can tear and split into pieces.
 pub struct Becker2009Elasticity<
     N: RealField,
     KernelDensity: Kernel = CubicSplineKernel,
@@ -47,12 +57,24 @@ pub struct Becker2009Elasticity<
     d1: N,
     d2: N,
     nonlinear_strain: bool,
-    volumes0: Vec<N>,
-    positions0: Vec<Point<N>>,
-    contacts0: ParticlesContacts<N>,
+    /// The parameters of the phase transitions between the solid and liquid states of the particles.
+    ///
+    /// If this is `None` (which is the default), all the particles remain solid.
+    pub phase_transition: Option<PhaseTransition<N>>,
+    /// The parameters of the plastic deformation of the particles.
+    ///
+    /// If this is `None` (which is the default), the particles always go back to their rest shape.
+    pub plasticity: Option<Plasticity<N>>,
+    /// The parameters of the fracture of the rest contacts between the particles.
+    ///
+    /// If this is `None` (which is the default), the rest contacts never break.
+    pub fracture: Option<Fracture<N>>,
+    phases: Vec<ParticlePhase>,
+    rest_state: ElasticRestState<N>,
     rotations: Vec<RotationMatrix<N>>,
     deformation_gradient_tr: Vec<Matrix<N>>,
     stress: Vec<SpatialVector<N>>,
+    plastic_strains: Vec<Matrix<N>>,
     phantom: PhantomData<(KernelDensity, KernelGradient)>,
 }
 
@@ -72,78 +94,171 @@ impl<N: RealField, KernelDensity: Kernel, KernelGradient: Kernel>
             d1,
             d2,
             nonlinear_strain,
-            volumes0: Vec::new(),
-            positions0: Vec::new(),
-            contacts0: ParticlesContacts::new(),
+            phase_transition: None,
+            plasticity: None,
+            fracture: None,
+            phases: Vec::new(),
+            rest_state: ElasticRestState::new(),
             rotations: Vec::new(),
             deformation_gradient_tr: Vec::new(),
             stress: Vec::new(),
+            plastic_strains: Vec::new(),
             phantom: PhantomData,
         }
     }
 
-    fn init(&mut self, kernel_radius: N, fluid: &Fluid<N>) {
-        let nparticles = fluid.positions.len();
-
-        if self.positions0.len() != nparticles {
-            self.positions0 = fluid.positions.clone();
-            self.volumes0.resize(nparticles, N::zero());
+    fn init(
+        &mut self,
+        kernel_radius: N,
+        fluid_fluid_contacts: &ParticlesContacts<N>,
+        fluid: &Fluid<N>,
+    ) {
+        let nparticles = fluid.num_particles();
+
+        if self.rest_state.needs_init() {
+            self.rest_state
+                .init::<KernelDensity, KernelGradient>(kernel_radius, fluid);
+            self.phases.resize(nparticles, ParticlePhase::Solid);
+            self.rotations = vec![RotationMatrix::identity(); nparticles];
+            self.deformation_gradient_tr = vec![Matrix::identity(); nparticles];
+            self.stress = vec![SpatialVector::zeros(); nparticles];
+            self.plastic_strains = vec![Matrix::zeros(); nparticles];
+
+            // The particles that were liquid before the reset stay liquid.
+            let liquid: Vec<_> = (0..nparticles)
+                .filter(|i| self.phases[*i] == ParticlePhase::Liquid)
+                .collect();
+
+            if !liquid.is_empty() {
+                self.rest_state.detach_particles(&liquid);
+                self.rest_state.compute_volumes0(fluid);
+            }
+        } else if self.rest_state.len() < nparticles {
+            self.phases.resize(nparticles, ParticlePhase::Solid);
             self.rotations
                 .resize(nparticles, RotationMatrix::identity());
             self.deformation_gradient_tr
                 .resize(nparticles, Matrix::identity());
             self.stress.resize(nparticles, SpatialVector::zeros());
-            geometry::compute_self_contacts(kernel_radius, fluid, &mut self.contacts0);
+            self.plastic_strains.resize(nparticles, Matrix::zeros());
+
+            let phases = &self.phases;
+            self.rest_state
+                .insert_particles::<KernelDensity, KernelGradient>(
+                    kernel_radius,
+                    fluid_fluid_contacts,
+                    fluid,
+                    &mut self.rotations,
+                    |j| phases[j] == ParticlePhase::Solid,
+                );
+        }
+    }
 
-            for contacts in self.contacts0.contacts_mut() {
-                for c in contacts.get_mut().unwrap() {
-                    let p1 = &self.positions0[c.i];
-                    let p2 = &self.positions0[c.j];
-                    c.weight = KernelDensity::points_apply(p1, p2, kernel_radius);
-                    c.gradient = KernelGradient::points_apply_diff1(p1, p2, kernel_radius);
+    /// The phase of each particle of the fluid this elasticity is attached to.
+    pub fn phases(&self) -> &[ParticlePhase] {
+        &self.phases
+    }
 
-                    self.volumes0[c.i] += fluid.particle_mass(c.j) * c.weight;
-                    self.volumes0[c.j] += fluid.particle_mass(c.i) * c.weight;
-                }
-            }
+    /// The rest configuration of the particles of the fluid this elasticity is attached to.
+    pub fn rest_state(&self) -> &ElasticRestState<N> {
+        &self.rest_state
+    }
 
-            for i in 0..nparticles {
-                self.volumes0[i] = fluid.particle_mass(i) / self.volumes0[i];
-            }
-        }
+    /// Resets the rest shape so it matches the current particle positions the next time this elasticity is applied.
+    ///
+    /// This also clears the plastic deformations of the particles. The liquid particles remain liquid.
+    pub fn reset_rest_shape(&mut self) {
+        self.rest_state.reset();
     }
 
-    fn compute_rotations(&mut self, _kernel_radius: N, fluid: &Fluid<N>) {
-        let _2: N = na::convert(2.0f64);
+    /// Sets the rest position of each particle of the fluid this elasticity is attached to.
+    ///
+    /// This will take effect the next time this elasticity is applied, at which point `positions0`
+    /// must contain exactly one point per particle of the fluid. This also clears the plastic
+    /// deformations of the particles. The liquid particles remain liquid.
+    pub fn set_rest_shape(&mut self, positions0: Vec<Point<N>>) {
+        self.rest_state.set_positions0(positions0);
+    }
 
-        let contacts0 = &self.contacts0;
-        let positions0 = &self.positions0;
+    // Melts and solidifies the particles depending on their temperatures and stresses.
+    fn update_phases(
+        &mut self,
+        kernel_radius: N,
+        fluid_fluid_contacts: &ParticlesContacts<N>,
+        fluid: &Fluid<N>,
+    ) {
+        let transition = match &self.phase_transition {
+            Some(transition) => *transition,
+            None => return,
+        };
+
+        let mut melted = Vec::new();
+        let mut solidified = Vec::new();
+
+        for (i, phase) in self.phases.iter().enumerate() {
+            let temperature = fluid.temperatures[i];
+
+            match phase {
+                ParticlePhase::Solid => {
+                    let overstressed = transition
+                        .melting_stress
+                        .map(|max_stress| self.stress[i].norm() > max_stress)
+                        .unwrap_or(false);
+
+                    if temperature > transition.melting_temperature || overstressed {
+                        melted.push(i);
+                    }
+                }
+                ParticlePhase::Liquid => {
+                    if temperature < transition.solidification_temperature {
+                        solidified.push(i);
+                    }
+                }
+            }
+        }
 
-        par_iter_mut!(&mut self.rotations)
-            .enumerate()
-            .for_each(|(i, rotation)| {
-                let mut a_pq = Matrix::zeros();
+        if melted.is_empty() && solidified.is_empty() {
+            return;
+        }
 
-                for c in contacts0.particle_contacts(i).read().unwrap().iter() {
-                    let p_ji = fluid.positions[c.j] - fluid.positions[c.i];
-                    let p0_ji = positions0[c.j] - positions0[c.i];
-                    let coeff = c.weight * fluid.particle_mass(c.j);
-                    a_pq += p_ji * (p0_ji * coeff).transpose();
-                }
+        // Remove all the rest contacts of the melted particles.
+        for i in &melted {
+            self.phases[*i] = ParticlePhase::Liquid;
+            self.stress[*i] = SpatialVector::zeros();
+            self.deformation_gradient_tr[*i] = Matrix::zeros();
+            self.plastic_strains[*i] = Matrix::zeros();
+        }
 
-                // Extract the rotation matrix.
-                *rotation =
-                    RotationMatrix::from_matrix_eps(&a_pq, N::default_epsilon(), 20, *rotation);
-            })
+        self.rest_state.detach_particles(&melted);
+
+        // Compute the rest positions and create the rest contacts of the solidified particles
+        // from their solid neighbors.
+        let phases = &self.phases;
+        self.rest_state
+            .attach_particles::<KernelDensity, KernelGradient>(
+                kernel_radius,
+                fluid_fluid_contacts,
+                fluid,
+                &mut self.rotations,
+                &solidified,
+                |j| phases[j] == ParticlePhase::Solid,
+            );
+
+        for i in &solidified {
+            self.phases[*i] = ParticlePhase::Solid;
+            self.plastic_strains[*i] = Matrix::zeros();
+        }
+
+        self.rest_state.compute_volumes0(fluid);
     }
 
     fn compute_stresses(&mut self, _kernel_radius: N, fluid: &Fluid<N>) {
         let _2: N = na::convert(2.0f64);
         let _0_5: N = na::convert(0.564);
 
-        let contacts0 = &self.contacts0;
+        let contacts0 = &self.rest_state.contacts0;
         let rotations = &self.rotations;
-        let positions0 = &self.positions0;
+        let positions0 = &self.rest_state.positions0;
 
         // let _0 = N::zero();
         // let c = Matrix::new(
@@ -170,7 +285,8 @@ impl<N: RealField, KernelDensity: Kernel, KernelGradient: Kernel>
         let d2 = self.d2;
 
         let nonlinear_strain = self.nonlinear_strain;
-        let volumes0 = &self.volumes0;
+        let volumes0 = &self.rest_state.volumes0;
+        let plastic_strains = &self.plastic_strains;
 
         par_iter_mut!(&mut self.deformation_gradient_tr)
             .zip(&mut self.stress)
@@ -186,12 +302,14 @@ impl<N: RealField, KernelDensity: Kernel, KernelGradient: Kernel>
                 }
 
                 *deformation_grad_tr = grad_tr;
+                // Only the elastic part of the strain results in a stress.
+                let plastic_strain = &plastic_strains[i];
 
                 #[cfg(feature = "dim3")]
                 {
                     if nonlinear_strain {
                         let j = grad_tr + Matrix::identity();
-                        let jjt = j * j.transpose();
+                        let jjt = j * j.transpose() - plastic_strain * _2;
 
                         let stress012 = c_top_left
                             * Vector::new(
@@ -209,6 +327,7 @@ impl<N: RealField, KernelDensity: Kernel, KernelGradient: Kernel>
                             jjt.m32 * _0_5 * d2,
                         );
                     } else {
+                        let grad_tr = grad_tr - plastic_strain;
                         // let strain = Vector::new(
                         //     grad_tr.m11,
                         //     grad_tr.m22,
@@ -235,12 +354,13 @@ impl<N: RealField, KernelDensity: Kernel, KernelGradient: Kernel>
                 {
                     if nonlinear_strain {
                         let j = grad_tr + Matrix::identity();
-                        let jjt = j * j.transpose();
+                        let jjt = j * j.transpose() - plastic_strain * _2;
 
                         let stress01 =
                             c_top_left * Vector::new(jjt.m11 - N::one(), jjt.m22 - N::one()) * _0_5;
                         *stress = SpatialVector::new(stress01.x, stress01.y, jjt.m21 * _0_5 * d2);
                     } else {
+                        let grad_tr = grad_tr - plastic_strain;
                         // let strain = Vector::new(
                         //     grad_tr.m11,
                         //     grad_tr.m22,
@@ -260,6 +380,81 @@ impl<N: RealField, KernelDensity: Kernel, KernelGradient: Kernel>
                 }
             })
     }
+
+    // Removes the rest contacts between the particles stretched beyond the maximum strain.
+    fn update_fractures(&mut self, fluid: &Fluid<N>) {
+        let fracture = match &self.fracture {
+            Some(fracture) => *fracture,
+            None => return,
+        };
+
+        let _0_5: N = na::convert(0.5);
+        let max_stretch = N::one() + fracture.max_strain;
+        let positions0 = &self.rest_state.positions0;
+        let plastic_strains = &self.plastic_strains;
+        let mut num_broken_contacts = 0;
+
+        for contacts in self.rest_state.contacts0.contacts_mut() {
+            let contacts = contacts.get_mut().unwrap();
+            let num_contacts = contacts.len();
+
+            contacts.retain(|c| {
+                if c.i == c.j {
+                    return true;
+                }
+
+                // The rest distance accounts for the plastic deformation of both particles.
+                let p0_ji = positions0[c.j] - positions0[c.i];
+                let plastic_strain = (plastic_strains[c.i] + plastic_strains[c.j]) * _0_5;
+                let dist0 = (p0_ji + plastic_strain * p0_ji).norm();
+                let dist = na::distance(&fluid.positions[c.i], &fluid.positions[c.j]);
+                dist <= dist0 * max_stretch
+            });
+
+            num_broken_contacts += num_contacts - contacts.len();
+        }
+
+        if num_broken_contacts != 0 {
+            self.rest_state.compute_volumes0(fluid);
+        }
+    }
+
+    // Converts the part of the strain exceeding the yield strain into plastic strain.
+    fn update_plastic_strains(&mut self, timestep: &TimestepManager<N>) {
+        let plasticity = match &self.plasticity {
+            Some(plasticity) => *plasticity,
+            None => return,
+        };
+
+        let _0_5: N = na::convert(0.5);
+        let creep = (plasticity.creep_rate * timestep.dt()).min(N::one());
+        let nonlinear_strain = self.nonlinear_strain;
+        let phases = &self.phases;
+        let deformation_gradient_tr = &self.deformation_gradient_tr;
+
+        par_iter_mut!(self.plastic_strains)
+            .enumerate()
+            .for_each(|(i, plastic_strain)| {
+                if phases[i] != ParticlePhase::Solid {
+                    return;
+                }
+
+                let grad_tr = &deformation_gradient_tr[i];
+                let strain = if nonlinear_strain {
+                    let j = grad_tr + Matrix::identity();
+                    (j * j.transpose() - Matrix::identity()) * _0_5
+                } else {
+                    (grad_tr + grad_tr.transpose()) * _0_5
+                };
+                let elastic_strain = strain - *plastic_strain;
+                let norm = elastic_strain.norm();
+
+                if norm > plasticity.yield_strain {
+                    *plastic_strain +=
+                        elastic_strain * ((N::one() - plasticity.yield_strain / norm) * creep);
+                }
+            })
+    }
 }
 
 impl<N: RealField, KernelDensity: Kernel, KernelGradient: Kernel> NonPressureForce<N>
@@ -267,23 +462,27 @@ impl<N: RealField, KernelDensity: Kernel, KernelGradient: Kernel> NonPressureFor
 {
     fn solve(
         &mut self,
-        _timestep: &TimestepManager<N>,
+        _counters: &mut Counters,
+        timestep: &TimestepManager<N>,
         kernel_radius: N,
-        _fluid_fluid_contacts: &ParticlesContacts<N>,
+        fluid_fluid_contacts: &ParticlesContacts<N>,
         _fluid_boundaries_contacts: &ParticlesContacts<N>,
         fluid: &mut Fluid<N>,
         _boundaries: &[Boundary<N>],
         _densities: &[N],
     ) {
-        self.init(kernel_radius, fluid);
+        self.init(kernel_radius, fluid_fluid_contacts, fluid);
+        self.update_phases(kernel_radius, fluid_fluid_contacts, fluid);
+        self.update_fractures(fluid);
 
         let _0_5: N = na::convert(0.5f64);
-        self.compute_rotations(kernel_radius, fluid);
+        self.rest_state
+            .compute_rotations(fluid, &mut self.rotations);
         self.compute_stresses(kernel_radius, fluid);
 
         // Compute and apply forces.
-        let contacts0 = &self.contacts0;
-        let volumes0 = &self.volumes0;
+        let contacts0 = &self.rest_state.contacts0;
+        let volumes0 = &self.rest_state.volumes0;
         let deformation_gradient_tr = &self.deformation_gradient_tr;
         let rotations = &self.rotations;
         let stress = &self.stress;
@@ -331,12 +530,27 @@ impl<N: RealField, KernelDensity: Kernel, KernelGradient: Kernel> NonPressureFor
                     }
                 })
         }
+
+        self.update_plastic_strains(timestep);
     }
 
     fn apply_permutation(&mut self, permutation: &[usize]) {
-        self.volumes0 = crate::z_order::apply_permutation(permutation, &self.volumes0);
-        self.positions0 = crate::z_order::apply_permutation(permutation, &self.positions0);
+        self.rest_state.apply_permutation(permutation);
         self.rotations = crate::z_order::apply_permutation(permutation, &self.rotations);
-        self.contacts0.apply_permutation(permutation);
+        self.phases = crate::z_order::apply_permutation(permutation, &self.phases);
+        self.plastic_strains =
+            crate::z_order::apply_permutation(permutation, &self.plastic_strains);
+    }
+
+    fn apply_particles_removal(&mut self, deleted_particles: &[bool]) {
+        self.rest_state.apply_particles_removal(deleted_particles);
+
+        // The particles added since the last step are at the end of the mask, and have no state yet.
+        let deleted_particles = &deleted_particles[..self.phases.len()];
+        crate::helper::filter_from_mask(deleted_particles, &mut self.phases);
+        crate::helper::filter_from_mask(deleted_particles, &mut self.rotations);
+        crate::helper::filter_from_mask(deleted_particles, &mut self.deformation_gradient_tr);
+        crate::helper::filter_from_mask(deleted_particles, &mut self.stress);
+        crate::helper::filter_from_mask(deleted_particles, &mut self.plastic_strains);
     }
 }
@@ -0,0 +1,424 @@
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+use na::RealField;
+
+use crate::geometry::{self, Contact, ParticlesContacts};
+use crate::kernel::Kernel;
+use crate::math::{Matrix, Point, RotationMatrix, Vector};
+use crate::object::Fluid;
+
+/// The rest configuration of an elastic fluid.
+///
+/// This stores the rest position and rest volume of each particle, as well as the rest contacts
+/// between the particles that are neighbors in the rest configuration. It is initialized from the
+/// current positions of the particles the first time an elasticity model is applied to its fluid, and
+/// can be reset explicitly with `self.reset()` or `self.set_positions0(...)`.
+///
+/// The particles added to the fluid afterwards are attached to the existing rest configuration (their
+/// rest positions are deduced from their neighbors), and the particles removed from the fluid are removed
+/// from the rest configuration without affecting the others.
+pub struct ElasticRestState<N: RealField> {
+    pub(crate) positions0: Vec<Point<N>>,
+    pub(crate) volumes0: Vec<N>,
+    pub(crate) contacts0: ParticlesContacts<N>,
+    needs_init: bool,
+}
+
+impl<N: RealField> ElasticRestState<N> {
+    /// Initializes an empty rest configuration.
+    pub fn new() -> Self {
+        Self {
+            positions0: Vec::new(),
+            volumes0: Vec::new(),
+            contacts0: ParticlesContacts::new(),
+            needs_init: true,
+        }
+    }
+
+    /// The number of particles of this rest configuration.
+    pub fn len(&self) -> usize {
+        self.positions0.len()
+    }
+
+    /// Returns `true` if this rest configuration has not been initialized yet.
+    pub fn is_empty(&self) -> bool {
+        self.positions0.is_empty()
+    }
+
+    /// The rest position of each particle.
+    pub fn positions0(&self) -> &[Point<N>] {
+        &self.positions0
+    }
+
+    /// The rest volume of each particle.
+    pub fn volumes0(&self) -> &[N] {
+        &self.volumes0
+    }
+
+    /// The contacts between the particles that are neighbors in the rest configuration.
+    pub fn contacts0(&self) -> &ParticlesContacts<N> {
+        &self.contacts0
+    }
+
+    /// Resets this rest configuration so it is initialized again from the current particle positions
+    /// the next time the elasticity model is applied.
+    pub fn reset(&mut self) {
+        self.positions0.clear();
+        self.needs_init = true;
+    }
+
+    /// Sets the rest position of each particle.
+    ///
+    /// The rest volumes and rest contacts are recomputed from these positions the next time the elasticity
+    /// model is applied. At this time, `positions0` must contain exactly one point per particle of the fluid.
+    pub fn set_positions0(&mut self, positions0: Vec<Point<N>>) {
+        self.positions0 = positions0;
+        self.needs_init = true;
+    }
+
+    // Returns `true` if the rest volumes and rest contacts must be recomputed from scratch.
+    pub(crate) fn needs_init(&self) -> bool {
+        self.needs_init || self.positions0.is_empty()
+    }
+
+    // Initializes the rest configuration from the rest positions given by the user, or from the current
+    // particle positions if there is none.
+    pub(crate) fn init<KernelDensity: Kernel, KernelGradient: Kernel>(
+        &mut self,
+        kernel_radius: N,
+        fluid: &Fluid<N>,
+    ) {
+        if self.positions0.is_empty() {
+            self.positions0 = fluid.positions.clone();
+        }
+
+        assert_eq!(
+            self.positions0.len(),
+            fluid.num_particles(),
+            "The number of rest positions must be equal to the number of particles of the fluid."
+        );
+
+        self.volumes0 = vec![N::zero(); fluid.num_particles()];
+        geometry::compute_points_self_contacts(
+            kernel_radius,
+            &self.positions0,
+            &mut self.contacts0,
+        );
+
+        for contacts in self.contacts0.contacts_mut() {
+            for c in contacts.get_mut().unwrap() {
+                let p1 = &self.positions0[c.i];
+                let p2 = &self.positions0[c.j];
+                c.weight = KernelDensity::points_apply(p1, p2, kernel_radius);
+                c.gradient = KernelGradient::points_apply_diff1(p1, p2, kernel_radius);
+            }
+        }
+
+        self.compute_volumes0(fluid);
+        self.needs_init = false;
+    }
+
+    // Attaches the particles added to the fluid since the last update to the rest configuration.
+    //
+    // The rotations of the new particles must already be allocated. They are only attached to the existing
+    // particles `j` such that `is_attached(j)` is `true`.
+    pub(crate) fn insert_particles<KernelDensity: Kernel, KernelGradient: Kernel>(
+        &mut self,
+        kernel_radius: N,
+        fluid_fluid_contacts: &ParticlesContacts<N>,
+        fluid: &Fluid<N>,
+        rotations: &mut [RotationMatrix<N>],
+        is_attached: impl Fn(usize) -> bool,
+    ) {
+        let first_new = self.positions0.len();
+        let new_particles: Vec<_> = (first_new..fluid.num_particles()).collect();
+
+        self.positions0
+            .extend_from_slice(&fluid.positions[first_new..]);
+        self.volumes0.resize(fluid.num_particles(), N::zero());
+        self.contacts0.resize(fluid.num_particles());
+
+        self.attach_particles::<KernelDensity, KernelGradient>(
+            kernel_radius,
+            fluid_fluid_contacts,
+            fluid,
+            rotations,
+            &new_particles,
+            |j| j < first_new && is_attached(j),
+        );
+        self.compute_volumes0(fluid);
+    }
+
+    // Computes the rest positions and the rest contacts of the given particles, which have no rest contacts yet.
+    //
+    // The rest position of each particle is deduced from its current neighbors `j` such that `is_attached(j)`
+    // is `true`. Then rest contacts are created between the given particles and these neighbors, as well as
+    // between the given particles themselves. The rest volumes are not updated.
+    pub(crate) fn attach_particles<KernelDensity: Kernel, KernelGradient: Kernel>(
+        &mut self,
+        kernel_radius: N,
+        fluid_fluid_contacts: &ParticlesContacts<N>,
+        fluid: &Fluid<N>,
+        rotations: &mut [RotationMatrix<N>],
+        particles: &[usize],
+        is_attached: impl Fn(usize) -> bool,
+    ) {
+        let mut attached_now = vec![false; fluid.num_particles()];
+
+        for i in particles {
+            attached_now[*i] = true;
+        }
+
+        // Compute the rest positions from the attached neighbors.
+        for i in particles {
+            let mut position0 = Vector::zeros();
+            let mut num_attached_neighbors = 0;
+            let mut rotation = RotationMatrix::identity();
+
+            for c in fluid_fluid_contacts
+                .particle_contacts(*i)
+                .read()
+                .unwrap()
+                .iter()
+            {
+                if c.is_same_model_contact() && !attached_now[c.j] && is_attached(c.j) {
+                    let x_ji = fluid.positions[*i] - fluid.positions[c.j];
+                    position0 += self.positions0[c.j].coords
+                        + rotations[c.j].inverse_transform_vector(&x_ji);
+                    rotation = rotations[c.j];
+                    num_attached_neighbors += 1;
+                }
+            }
+
+            if num_attached_neighbors == 0 {
+                self.positions0[*i] = fluid.positions[*i];
+            } else {
+                self.positions0[*i] =
+                    Point::from(position0 / na::convert::<_, N>(num_attached_neighbors as f64));
+            }
+
+            rotations[*i] = rotation;
+        }
+
+        // Create the rest contacts.
+        let contacts0 = self.contacts0.contacts_mut();
+
+        for i in particles {
+            for c in fluid_fluid_contacts
+                .particle_contacts(*i)
+                .read()
+                .unwrap()
+                .iter()
+            {
+                if !c.is_same_model_contact() || !(attached_now[c.j] || is_attached(c.j)) {
+                    continue;
+                }
+
+                let p1 = &self.positions0[*i];
+                let p2 = &self.positions0[c.j];
+
+                if na::distance_squared(p1, p2) > kernel_radius * kernel_radius {
+                    continue;
+                }
+
+                let contact = Contact {
+                    i: *i,
+                    i_model: 0,
+                    j: c.j,
+                    j_model: 0,
+                    weight: KernelDensity::points_apply(p1, p2, kernel_radius),
+                    gradient: KernelGradient::points_apply_diff1(p1, p2, kernel_radius),
+                };
+
+                contacts0[*i].get_mut().unwrap().push(contact);
+
+                // If the neighbor is also being attached, this contact flipped will be
+                // created when processing this neighbor.
+                if !attached_now[c.j] {
+                    contacts0[c.j].get_mut().unwrap().push(contact.flip());
+                }
+            }
+        }
+    }
+
+    // Removes all the rest contacts of the given particles. The rest volumes are not updated.
+    pub(crate) fn detach_particles(&mut self, particles: &[usize]) {
+        let contacts0 = self.contacts0.contacts_mut();
+
+        for i in particles {
+            let neighbors: Vec<_> = contacts0[*i]
+                .get_mut()
+                .unwrap()
+                .drain(..)
+                .map(|c| c.j)
+                .collect();
+
+            for j in neighbors {
+                contacts0[j].get_mut().unwrap().retain(|c| c.j != *i);
+            }
+        }
+    }
+
+    pub(crate) fn compute_volumes0(&mut self, fluid: &Fluid<N>) {
+        self.volumes0.iter_mut().for_each(|v| *v = N::zero());
+
+        for contacts in self.contacts0.contacts_mut() {
+            for c in contacts.get_mut().unwrap() {
+                self.volumes0[c.i] += fluid.particle_mass(c.j) * c.weight;
+                self.volumes0[c.j] += fluid.particle_mass(c.i) * c.weight;
+            }
+        }
+
+        for i in 0..self.volumes0.len() {
+            if self.volumes0[i].is_zero() {
+                // This is a liquid particle.
+                self.volumes0[i] = fluid.volumes[i];
+            } else {
+                self.volumes0[i] = fluid.particle_mass(i) / self.volumes0[i];
+            }
+        }
+    }
+
+    // Computes the rotation of each particle from the current and rest positions of its rest neighbors.
+    pub(crate) fn compute_rotations(&self, fluid: &Fluid<N>, rotations: &mut [RotationMatrix<N>]) {
+        let contacts0 = &self.contacts0;
+        let positions0 = &self.positions0;
+
+        par_iter_mut!(rotations)
+            .enumerate()
+            .for_each(|(i, rotation)| {
+                let mut a_pq = Matrix::zeros();
+
+                for c in contacts0.particle_contacts(i).read().unwrap().iter() {
+                    let p_ji = fluid.positions[c.j] - fluid.positions[c.i];
+                    let p0_ji = positions0[c.j] - positions0[c.i];
+                    let coeff = c.weight * fluid.particle_mass(c.j);
+                    a_pq += p_ji * (p0_ji * coeff).transpose();
+                }
+
+                // Extract the rotation matrix.
+                *rotation =
+                    RotationMatrix::from_matrix_eps(&a_pq, N::default_epsilon(), 20, *rotation);
+            })
+    }
+
+    pub(crate) fn apply_permutation(&mut self, permutation: &[usize]) {
+        self.volumes0 = crate::z_order::apply_permutation(permutation, &self.volumes0);
+        self.positions0 = crate::z_order::apply_permutation(permutation, &self.positions0);
+        self.contacts0.apply_permutation(permutation);
+    }
+
+    // Removes the deleted particles without affecting the rest configuration of the others.
+    //
+    // The mask also covers the particles added to the fluid since the last update. They are
+    // appended after the particles of this rest configuration, so only the prefix of the mask applies.
+    pub(crate) fn apply_particles_removal(&mut self, deleted_particles: &[bool]) {
+        let deleted_particles = &deleted_particles[..self.positions0.len()];
+
+        crate::helper::filter_from_mask(deleted_particles, &mut self.positions0);
+
+        if !self.needs_init {
+            crate::helper::filter_from_mask(deleted_particles, &mut self.volumes0);
+            self.contacts0.apply_particles_removal(deleted_particles);
+        }
+    }
+}
+
+impl<N: RealField> Default for ElasticRestState<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ElasticRestState;
+    use crate::geometry::ParticlesContacts;
+    use crate::kernel::CubicSplineKernel;
+    use crate::math::{Point, RotationMatrix, Vector};
+    use crate::object::Fluid;
+
+    #[test]
+    fn particles_removal_keeps_rest_shape() {
+        let positions: Vec<_> = (0..4)
+            .map(|i| Point::from(Vector::x() * (i as f64 * 0.1)))
+            .collect();
+        let fluid = Fluid::new(positions.clone(), 0.05, 1000.0);
+        let mut rest_state = ElasticRestState::new();
+        rest_state.init::<CubicSplineKernel, CubicSplineKernel>(0.25, &fluid);
+
+        rest_state.apply_particles_removal(&[false, true, false, false]);
+
+        assert!(!rest_state.needs_init());
+        assert_eq!(
+            rest_state.positions0(),
+            &[positions[0], positions[2], positions[3]]
+        );
+        assert_eq!(rest_state.volumes0().len(), 3);
+
+        for c in rest_state.contacts0().contacts().iter() {
+            for c in c.read().unwrap().iter() {
+                assert!(c.i < 3 && c.j < 3);
+                let distance =
+                    na::distance(&rest_state.positions0()[c.i], &rest_state.positions0()[c.j]);
+                assert!(distance <= 0.25);
+            }
+        }
+
+        // The rest contact between the first and third particles is kept.
+        assert!(rest_state
+            .contacts0()
+            .particle_contacts(0)
+            .read()
+            .unwrap()
+            .iter()
+            .any(|c| c.j == 1));
+    }
+
+    #[test]
+    fn particles_added_and_removed_in_the_same_step() {
+        let positions0: Vec<_> = (0..4)
+            .map(|i| Point::from(Vector::x() * (i as f64 * 0.1)))
+            .collect();
+        let mut fluid = Fluid::new(positions0.clone(), 0.05, 1000.0);
+        let mut rest_state = ElasticRestState::new();
+        rest_state.init::<CubicSplineKernel, CubicSplineKernel>(0.25, &fluid);
+
+        // Deform the fluid so the current and rest positions differ.
+        fluid.positions.iter_mut().for_each(|p| *p *= 2.0);
+        fluid.add_particles(&[Point::from(Vector::x() * 0.8)], None);
+        fluid.delete_particle_at_next_timestep(1);
+
+        rest_state.apply_particles_removal(fluid.deleted_particles_mask());
+        fluid.apply_particles_removal();
+
+        assert_eq!(
+            rest_state.positions0(),
+            &[positions0[0], positions0[2], positions0[3]]
+        );
+
+        let mut contacts = ParticlesContacts::new();
+        contacts.resize(fluid.num_particles());
+        let mut rotations = vec![RotationMatrix::identity(); fluid.num_particles()];
+        rest_state.insert_particles::<CubicSplineKernel, CubicSplineKernel>(
+            0.25,
+            &contacts,
+            &fluid,
+            &mut rotations,
+            |_| true,
+        );
+
+        assert_eq!(
+            rest_state.positions0(),
+            &[
+                positions0[0],
+                positions0[2],
+                positions0[3],
+                fluid.positions[3]
+            ]
+        );
+        assert_eq!(rest_state.volumes0().len(), 4);
+        assert_eq!(rest_state.contacts0().contacts().len(), 4);
+    }
+}
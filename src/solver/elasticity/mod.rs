@@ -1,3 +1,11 @@
 pub use self::becker2009_elasticity::Becker2009Elasticity;
+pub use self::elastic_rest_state::ElasticRestState;
+pub use self::peer2018_elasticity::Peer2018Elasticity;
+pub use self::phase_transition::{ParticlePhase, PhaseTransition};
+pub use self::plasticity::{Fracture, Plasticity};
 
 mod becker2009_elasticity;
+mod elastic_rest_state;
+mod peer2018_elasticity;
+mod phase_transition;
+mod plasticity;
@@ -0,0 +1,434 @@
+use std::marker::PhantomData;
+
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+use na::{self, RealField};
+
+use crate::counters::Counters;
+use crate::geometry::ParticlesContacts;
+use crate::kernel::{CubicSplineKernel, Kernel};
+use crate::math::{Matrix, Point, RotationMatrix, Vector};
+use crate::object::{Boundary, Fluid};
+use crate::solver::{ElasticRestState, NonPressureForce};
+use crate::TimestepManager;
+
+fn lame_coefficients<N: RealField>(young_modulus: N, poisson_ratio: N) -> (N, N) {
+    let _1 = N::one();
+    let _2: N = na::convert(2.0);
+
+    let lambda =
+        (young_modulus * poisson_ratio) / ((_1 + poisson_ratio) * (_1 - _2 * poisson_ratio));
+    let mu = young_modulus / (_2 * (_1 + poisson_ratio));
+    (lambda, mu)
+}
+
+// The linear stress resulting from the given displacement gradient.
+fn linear_stress<N: RealField>(lambda: N, mu: N, displacement_gradient: &Matrix<N>) -> Matrix<N> {
+    let _2: N = na::convert(2.0);
+    let strain = (displacement_gradient + displacement_gradient.transpose()) / _2;
+    strain * (_2 * mu) + Matrix::identity() * (lambda * strain.trace())
+}
+
+// Computes `sum_j V0_j * u_ji * gradient_ij^T` over the rest neighbors `j` of `i`, where `u_ji` is
+// the relative displacement (or velocity) of `j` wrt. `i`, expressed in the rest frame of `i`.
+fn rest_gradient<N: RealField>(
+    i: usize,
+    rest_state: &ElasticRestState<N>,
+    relative: impl Fn(usize) -> Vector<N>,
+) -> Matrix<N> {
+    let mut gradient = Matrix::zeros();
+
+    for c in rest_state
+        .contacts0
+        .particle_contacts(i)
+        .read()
+        .unwrap()
+        .iter()
+    {
+        gradient += relative(c.j) * (c.gradient * rest_state.volumes0[c.j]).transpose();
+    }
+
+    gradient
+}
+
+// Computes the elastic force applied to `i` by the given stresses.
+fn elastic_force<N: RealField>(
+    i: usize,
+    rest_state: &ElasticRestState<N>,
+    rotations: &[RotationMatrix<N>],
+    stresses: &[Matrix<N>],
+) -> Vector<N> {
+    let stress_i = rotations[i] * stresses[i];
+    let mut force = Vector::zeros();
+
+    for c in rest_state
+        .contacts0
+        .particle_contacts(i)
+        .read()
+        .unwrap()
+        .iter()
+    {
+        let stress_j = rotations[c.j] * stresses[c.j];
+        force += (stress_i + stress_j) * c.gradient * rest_state.volumes0[c.j];
+    }
+
+    force * rest_state.volumes0[i]
+}
+
+/// Implicit elasticity based on the method from Peer et al. 2018.
+///
+/// The elastic forces result from a corotated linear stress. They are integrated implicitly by solving,
+/// with a matrix-free conjugate gradient, for the velocities consistent with the stress at the end of the
+/// timestep. Unlike `Becker2009Elasticity`, this remains stable for stiff materials with large timesteps.
+pub struct Peer2018Elasticity<
+    N: RealField,
+    KernelDensity: Kernel = CubicSplineKernel,
+    KernelGradient: Kernel = CubicSplineKernel,
+> {
+    /// Maximum number of iterations of the conjugate gradient.
+    pub max_elasticity_iter: usize,
+    /// Maximum acceptable relative residual of the conjugate gradient.
+    ///
+    /// The elasticity solver will continue iterating until the norm of the residual divided by the norm
+    /// of the right-hand-side drops bellow this threshold, or until the maximum number of iterations is reached.
+    pub max_elasticity_error: N,
+    lambda: N,
+    mu: N,
+    rest_state: ElasticRestState<N>,
+    rotations: Vec<RotationMatrix<N>>,
+    stresses: Vec<Matrix<N>>,
+    predicted_velocities: Vec<Vector<N>>,
+    inv_diag: Vec<N>,
+    rhs: Vec<Vector<N>>,
+    solution: Vec<Vector<N>>,
+    residual: Vec<Vector<N>>,
+    preconditioned_residual: Vec<Vector<N>>,
+    direction: Vec<Vector<N>>,
+    matrix_times_direction: Vec<Vector<N>>,
+    phantom: PhantomData<(KernelDensity, KernelGradient)>,
+}
+
+impl<N: RealField, KernelDensity: Kernel, KernelGradient: Kernel>
+    Peer2018Elasticity<N, KernelDensity, KernelGradient>
+{
+    /// Initialize elasticity from its young modulus and poisson ratio.
+    pub fn new(young_modulus: N, poisson_ratio: N) -> Self {
+        let (lambda, mu) = lame_coefficients(young_modulus, poisson_ratio);
+
+        Self {
+            max_elasticity_iter: 100,
+            max_elasticity_error: na::convert(0.01),
+            lambda,
+            mu,
+            rest_state: ElasticRestState::new(),
+            rotations: Vec::new(),
+            stresses: Vec::new(),
+            predicted_velocities: Vec::new(),
+            inv_diag: Vec::new(),
+            rhs: Vec::new(),
+            solution: Vec::new(),
+            residual: Vec::new(),
+            preconditioned_residual: Vec::new(),
+            direction: Vec::new(),
+            matrix_times_direction: Vec::new(),
+            phantom: PhantomData,
+        }
+    }
+
+    /// The rest configuration of the particles of the fluid this elasticity is attached to.
+    pub fn rest_state(&self) -> &ElasticRestState<N> {
+        &self.rest_state
+    }
+
+    /// Resets the rest shape so it matches the current particle positions the next time this elasticity is applied.
+    pub fn reset_rest_shape(&mut self) {
+        self.rest_state.reset();
+    }
+
+    /// Sets the rest position of each particle of the fluid this elasticity is attached to.
+    ///
+    /// This will take effect the next time this elasticity is applied, at which point `positions0`
+    /// must contain exactly one point per particle of the fluid.
+    pub fn set_rest_shape(&mut self, positions0: Vec<Point<N>>) {
+        self.rest_state.set_positions0(positions0);
+    }
+
+    fn init(
+        &mut self,
+        kernel_radius: N,
+        fluid_fluid_contacts: &ParticlesContacts<N>,
+        fluid: &Fluid<N>,
+    ) {
+        let nparts = fluid.num_particles();
+
+        if self.rest_state.needs_init() {
+            self.rest_state
+                .init::<KernelDensity, KernelGradient>(kernel_radius, fluid);
+            self.rotations = vec![RotationMatrix::identity(); nparts];
+        } else if self.rest_state.len() < nparts {
+            self.rotations.resize(nparts, RotationMatrix::identity());
+            self.rest_state
+                .insert_particles::<KernelDensity, KernelGradient>(
+                    kernel_radius,
+                    fluid_fluid_contacts,
+                    fluid,
+                    &mut self.rotations,
+                    |_| true,
+                );
+        }
+
+        if self.rhs.len() != nparts {
+            self.stresses.resize(nparts, Matrix::zeros());
+            self.predicted_velocities.resize(nparts, Vector::zeros());
+            self.inv_diag.resize(nparts, N::zero());
+            self.rhs.resize(nparts, Vector::zeros());
+            self.solution.resize(nparts, Vector::zeros());
+            self.residual.resize(nparts, Vector::zeros());
+            self.preconditioned_residual.resize(nparts, Vector::zeros());
+            self.direction.resize(nparts, Vector::zeros());
+            self.matrix_times_direction.resize(nparts, Vector::zeros());
+        }
+    }
+
+    // Computes the stresses at the current positions, the right-hand-side, and the diagonal preconditioner.
+    fn setup_system(&mut self, timestep: &TimestepManager<N>, fluid: &Fluid<N>) {
+        let dt = timestep.dt();
+        let (lambda, mu) = (self.lambda, self.mu);
+        let rest_state = &self.rest_state;
+        let rotations = &self.rotations;
+
+        par_iter_mut!(self.stresses)
+            .enumerate()
+            .for_each(|(i, stress)| {
+                let displacement_gradient = rest_gradient(i, rest_state, |j| {
+                    rotations[i]
+                        .inverse_transform_vector(&(fluid.positions[j] - fluid.positions[i]))
+                        - (rest_state.positions0[j] - rest_state.positions0[i])
+                });
+                *stress = linear_stress(lambda, mu, &displacement_gradient);
+            });
+
+        par_iter_mut!(self.predicted_velocities)
+            .enumerate()
+            .for_each(|(i, v)| *v = fluid.velocities[i] + fluid.accelerations[i] * dt);
+
+        let stresses = &self.stresses;
+        let predicted_velocities = &self.predicted_velocities;
+        let stiffness = lambda + mu * na::convert(2.0);
+
+        par_iter_mut!(self.rhs)
+            .zip(par_iter_mut!(self.inv_diag))
+            .enumerate()
+            .for_each(|(i, (rhs_i, inv_diag_i))| {
+                let mi = fluid.particle_mass(i);
+                let force = elastic_force(i, rest_state, rotations, stresses);
+                *rhs_i = predicted_velocities[i] * mi + force * dt;
+
+                // Approximation of the diagonal of the system matrix.
+                let volume_i = rest_state.volumes0[i];
+                let mut diag = N::zero();
+
+                for c in rest_state
+                    .contacts0
+                    .particle_contacts(i)
+                    .read()
+                    .unwrap()
+                    .iter()
+                {
+                    diag += c.gradient.norm_squared() * rest_state.volumes0[c.j];
+                }
+
+                *inv_diag_i = N::one() / (mi + diag * stiffness * volume_i * volume_i * dt * dt);
+            })
+    }
+
+    // Computes `out = A * v` where `A` is the (symmetric positive-definite) system matrix.
+    fn apply_system_matrix(
+        timestep: &TimestepManager<N>,
+        lambda: N,
+        mu: N,
+        rest_state: &ElasticRestState<N>,
+        rotations: &[RotationMatrix<N>],
+        fluid: &Fluid<N>,
+        v: &[Vector<N>],
+        stress_changes: &mut [Matrix<N>],
+        out: &mut [Vector<N>],
+    ) {
+        let dt = timestep.dt();
+
+        par_iter_mut!(stress_changes)
+            .enumerate()
+            .for_each(|(i, stress_change)| {
+                let velocity_gradient = rest_gradient(i, rest_state, |j| {
+                    rotations[i].inverse_transform_vector(&(v[j] - v[i]))
+                });
+                *stress_change = linear_stress(lambda, mu, &velocity_gradient);
+            });
+
+        let stress_changes = &*stress_changes;
+
+        par_iter_mut!(out).enumerate().for_each(|(i, out_i)| {
+            let force_change = elastic_force(i, rest_state, rotations, stress_changes);
+            *out_i = v[i] * fluid.particle_mass(i) - force_change * (dt * dt);
+        })
+    }
+
+    fn solve_system(&mut self, timestep: &TimestepManager<N>, fluid: &Fluid<N>) -> usize {
+        let (lambda, mu) = (self.lambda, self.mu);
+
+        // NOTE: the stresses are no longer needed once the right-hand-side is computed, so their
+        // buffer is reused for the stress changes computed by `apply_system_matrix`.
+        // Warm-start with the predicted velocities.
+        self.solution.copy_from_slice(&self.predicted_velocities);
+        Self::apply_system_matrix(
+            timestep,
+            lambda,
+            mu,
+            &self.rest_state,
+            &self.rotations,
+            fluid,
+            &self.solution,
+            &mut self.stresses,
+            &mut self.matrix_times_direction,
+        );
+
+        let inv_diag = &self.inv_diag;
+        let rhs = &self.rhs;
+        let matrix_times_solution = &self.matrix_times_direction;
+
+        par_iter_mut!(self.residual)
+            .zip(par_iter_mut!(self.preconditioned_residual))
+            .zip(par_iter_mut!(self.direction))
+            .enumerate()
+            .for_each(|(i, ((r, z), p))| {
+                *r = rhs[i] - matrix_times_solution[i];
+                *z = *r * inv_diag[i];
+                *p = *z;
+            });
+
+        let rhs_norm = par_reduce_sum!(N::zero(), par_iter!(self.rhs).map(|b| b.norm_squared()))
+            .sqrt()
+            .max(N::default_epsilon());
+        let mut rz = par_reduce_sum!(
+            N::zero(),
+            par_iter!(self.residual)
+                .zip(par_iter!(self.preconditioned_residual))
+                .map(|(r, z)| r.dot(z))
+        );
+
+        for niter in 0..self.max_elasticity_iter {
+            let residual_norm = par_reduce_sum!(
+                N::zero(),
+                par_iter!(self.residual).map(|r| r.norm_squared())
+            )
+            .sqrt();
+
+            if residual_norm / rhs_norm <= self.max_elasticity_error || rz.is_zero() {
+                return niter;
+            }
+
+            Self::apply_system_matrix(
+                timestep,
+                lambda,
+                mu,
+                &self.rest_state,
+                &self.rotations,
+                fluid,
+                &self.direction,
+                &mut self.stresses,
+                &mut self.matrix_times_direction,
+            );
+
+            let p_ap = par_reduce_sum!(
+                N::zero(),
+                par_iter!(self.direction)
+                    .zip(par_iter!(self.matrix_times_direction))
+                    .map(|(p, ap)| p.dot(ap))
+            );
+
+            if p_ap <= N::zero() {
+                return niter;
+            }
+
+            let alpha = rz / p_ap;
+            let direction = &self.direction;
+            let matrix_times_direction = &self.matrix_times_direction;
+
+            par_iter_mut!(self.solution)
+                .zip(par_iter_mut!(self.residual))
+                .zip(par_iter_mut!(self.preconditioned_residual))
+                .enumerate()
+                .for_each(|(i, ((x, r), z))| {
+                    *x += direction[i] * alpha;
+                    *r -= matrix_times_direction[i] * alpha;
+                    *z = *r * inv_diag[i];
+                });
+
+            let new_rz = par_reduce_sum!(
+                N::zero(),
+                par_iter!(self.residual)
+                    .zip(par_iter!(self.preconditioned_residual))
+                    .map(|(r, z)| r.dot(z))
+            );
+            let beta = new_rz / rz;
+            rz = new_rz;
+
+            let preconditioned_residual = &self.preconditioned_residual;
+            par_iter_mut!(self.direction)
+                .enumerate()
+                .for_each(|(i, p)| *p = preconditioned_residual[i] + *p * beta);
+        }
+
+        self.max_elasticity_iter
+    }
+}
+
+impl<N: RealField, KernelDensity: Kernel, KernelGradient: Kernel> NonPressureForce<N>
+    for Peer2018Elasticity<N, KernelDensity, KernelGradient>
+{
+    fn solve(
+        &mut self,
+        counters: &mut Counters,
+        timestep: &TimestepManager<N>,
+        kernel_radius: N,
+        fluid_fluid_contacts: &ParticlesContacts<N>,
+        _fluid_boundaries_contacts: &ParticlesContacts<N>,
+        fluid: &mut Fluid<N>,
+        _boundaries: &[Boundary<N>],
+        _densities: &[N],
+    ) {
+        if timestep.dt().is_zero() {
+            return;
+        }
+
+        self.init(kernel_radius, fluid_fluid_contacts, fluid);
+        self.rest_state
+            .compute_rotations(fluid, &mut self.rotations);
+        self.setup_system(timestep, fluid);
+        counters.solver.elasticity_iterations += self.solve_system(timestep, fluid);
+
+        let inv_dt = timestep.inv_dt();
+        let solution = &self.solution;
+        let predicted_velocities = &self.predicted_velocities;
+
+        par_iter_mut!(fluid.accelerations)
+            .enumerate()
+            .for_each(|(i, acceleration)| {
+                *acceleration += (solution[i] - predicted_velocities[i]) * inv_dt;
+            })
+    }
+
+    fn apply_permutation(&mut self, permutation: &[usize]) {
+        self.rest_state.apply_permutation(permutation);
+        self.rotations = crate::z_order::apply_permutation(permutation, &self.rotations);
+    }
+
+    fn apply_particles_removal(&mut self, deleted_particles: &[bool]) {
+        self.rest_state.apply_particles_removal(deleted_particles);
+
+        // The particles added since the last step are at the end of the mask, and have no rotation yet.
+        let deleted_particles = &deleted_particles[..self.rotations.len()];
+        crate::helper::filter_from_mask(deleted_particles, &mut self.rotations);
+    }
+}
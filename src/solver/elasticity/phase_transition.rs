@@ -0,0 +1,37 @@
+use na::RealField;
+
+/// The phase of a particle of an elastic fluid.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ParticlePhase {
+    /// The particle is subject to elastic forces.
+    Solid,
+    /// The particle is not subject to elastic forces.
+    Liquid,
+}
+
+/// Parameters of the phase transitions between the solid and liquid states of an elastic fluid.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PhaseTransition<N: RealField> {
+    /// Solid particles with a temperature greater than this become liquid.
+    pub melting_temperature: N,
+    /// Liquid particles with a temperature smaller than this become solid.
+    ///
+    /// This should be smaller than `self.melting_temperature` to avoid particles
+    /// switching between both phases at each timestep.
+    pub solidification_temperature: N,
+    /// Solid particles with a stress norm greater than this become liquid.
+    ///
+    /// If this is `None`, particles never melt because of their stress.
+    pub melting_stress: Option<N>,
+}
+
+impl<N: RealField> PhaseTransition<N> {
+    /// Initializes phase transitions depending only on the particle temperatures.
+    pub fn new(melting_temperature: N, solidification_temperature: N) -> Self {
+        Self {
+            melting_temperature,
+            solidification_temperature,
+            melting_stress: None,
+        }
+    }
+}
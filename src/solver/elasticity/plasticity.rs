@@ -0,0 +1,46 @@
+use na::RealField;
+
+/// Parameters of the plastic deformation of an elastic fluid.
+///
+/// When the strain of a particle exceeds `self.yield_strain`, its rest position creeps toward its
+/// current position (relative to its neighbors) so the object keeps a part of its deformation.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Plasticity<N: RealField> {
+    /// The norm of the strain beyond which the particles deform plastically.
+    pub yield_strain: N,
+    /// The rate at which the rest shape creeps toward the deformed shape, per second.
+    ///
+    /// If this is greater than or equal to `1.0 / dt`, the strain exceeding the yield strain is
+    /// entirely converted into plastic deformation at each timestep.
+    pub creep_rate: N,
+}
+
+impl<N: RealField> Plasticity<N> {
+    /// Initializes plastic deformations with the given yield strain and creep rate.
+    pub fn new(yield_strain: N, creep_rate: N) -> Self {
+        Self {
+            yield_strain,
+            creep_rate,
+        }
+    }
+}
+
+/// Parameters of the fracture of an elastic fluid.
+///
+/// When the distance between two neighbor particles stretches too much compared to their rest
+/// distance, their rest contact is removed so they no longer exert elastic forces on each other.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Fracture<N: RealField> {
+    /// The maximum relative stretch between two particles before their rest contact breaks.
+    ///
+    /// For example, with a value of `0.5`, the rest contact between two particles breaks when
+    /// their distance becomes greater than 1.5 times their rest distance.
+    pub max_strain: N,
+}
+
+impl<N: RealField> Fracture<N> {
+    /// Initializes fractures with the given maximum strain.
+    pub fn new(max_strain: N) -> Self {
+        Self { max_strain }
+    }
+}
@@ -0,0 +1,217 @@
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+use na::{self, RealField};
+
+use crate::counters::Counters;
+use crate::geometry::ParticlesContacts;
+use crate::math::{Matrix, DIM};
+use crate::object::{Boundary, Fluid};
+use crate::solver::NonPressureForce;
+use crate::TimestepManager;
+
+/// Granular material (sand, snow, gravel, etc.) with a Drucker-Prager yield criterion.
+///
+/// Each particle carries a deviatoric stress integrated from the strain rate with a hypoelastic law
+/// (using the Jaumann stress rate), as in the elastic-plastic soil model of Bui et al. 2008. After each
+/// integration, the stress is projected back onto the Drucker-Prager yield surface, whose size depends on
+/// the friction angle, the cohesion, and the pressure of the particle. The pressure itself is the one computed
+/// by the pressure solver during the previous timestep (see `Fluid::pressures`), so the material stays
+/// incompressible and only the shear response is modeled here.
+///
+/// The boundaries are treated as granular material at rest (or moving with the boundary) with the same stress
+/// as the fluid particles in contact, so the granular material sticks to them until its stress exceeds the
+/// yield stress. This results in Coulomb friction, scaled by the `friction` coefficient of each boundary.
+///
+/// The stress is integrated explicitly, so the timestep must be small enough for the elastic waves to be stable,
+/// i.e., `sqrt(shear_modulus / density0) * dt` must be a fraction of the kernel radius.
+pub struct DruckerPragerGranular<N: RealField> {
+    /// The shear modulus of the granular material.
+    pub shear_modulus: N,
+    /// The internal friction angle of the granular material, in radians.
+    ///
+    /// This is close to the angle of repose of the material.
+    pub friction_angle: N,
+    /// The cohesion of the granular material.
+    ///
+    /// This is zero for dry sand.
+    pub cohesion: N,
+    stresses: Vec<Matrix<N>>,
+}
+
+impl<N: RealField> DruckerPragerGranular<N> {
+    /// Initializes a granular material with the given shear modulus, friction angle (in radians), and cohesion.
+    pub fn new(shear_modulus: N, friction_angle: N, cohesion: N) -> Self {
+        Self {
+            shear_modulus,
+            friction_angle,
+            cohesion,
+            stresses: Vec::new(),
+        }
+    }
+
+    /// The deviatoric stress of each particle of the fluid this granular material is attached to.
+    pub fn stresses(&self) -> &[Matrix<N>] {
+        &self.stresses
+    }
+
+    // The parameters `(alpha, k)` of the Drucker-Prager yield criterion `sqrt(J2) <= k + 3 * alpha * pressure`,
+    // matching the Mohr-Coulomb criterion in plane strain.
+    fn yield_parameters(&self) -> (N, N) {
+        let tan_phi = self.friction_angle.tan();
+        let denominator = (tan_phi * tan_phi * na::convert(12.0) + na::convert(9.0)).sqrt();
+        (
+            tan_phi / denominator,
+            self.cohesion * na::convert(3.0) / denominator,
+        )
+    }
+
+    fn update_stresses(
+        &mut self,
+        timestep: &TimestepManager<N>,
+        fluid_fluid_contacts: &ParticlesContacts<N>,
+        fluid_boundaries_contacts: &ParticlesContacts<N>,
+        fluid: &Fluid<N>,
+        boundaries: &[Boundary<N>],
+        densities: &[N],
+    ) {
+        let dt = timestep.dt();
+        let shear_modulus = self.shear_modulus;
+        let (alpha, k) = self.yield_parameters();
+        let _2: N = na::convert(2.0);
+        let _3: N = na::convert(3.0);
+        let inv_dim: N = na::convert(1.0 / DIM as f64);
+
+        par_iter_mut!(self.stresses)
+            .enumerate()
+            .for_each(|(i, stress)| {
+                let mut velocity_gradient = Matrix::zeros();
+                let mut weighted_pressure = N::zero();
+                let mut total_weight = N::zero();
+
+                for c in fluid_fluid_contacts
+                    .particle_contacts(i)
+                    .read()
+                    .unwrap()
+                    .iter()
+                {
+                    if c.i_model == c.j_model {
+                        let volume_j = fluid.particle_mass(c.j) / densities[c.j];
+                        let v_ji = fluid.velocities[c.j] - fluid.velocities[c.i];
+                        velocity_gradient += v_ji * (c.gradient * volume_j).transpose();
+                        weighted_pressure += fluid.pressures[c.j] * c.weight * volume_j;
+                        total_weight += c.weight * volume_j;
+                    }
+                }
+
+                for c in fluid_boundaries_contacts
+                    .particle_contacts(i)
+                    .read()
+                    .unwrap()
+                    .iter()
+                {
+                    let boundary = &boundaries[c.j_model];
+                    let v_ji = boundary.velocities[c.j] - fluid.velocities[c.i];
+                    velocity_gradient += v_ji
+                        * (c.gradient * (boundary.volumes[c.j] * boundary.friction)).transpose();
+                }
+
+                let strain_rate = (velocity_gradient + velocity_gradient.transpose()) / _2;
+                let spin_rate = (velocity_gradient - velocity_gradient.transpose()) / _2;
+                let deviatoric_strain_rate =
+                    strain_rate - Matrix::identity() * (strain_rate.trace() * inv_dim);
+
+                *stress += (deviatoric_strain_rate * (_2 * shear_modulus) + spin_rate * *stress
+                    - *stress * spin_rate)
+                    * dt;
+
+                // Return mapping onto the yield surface. The pressure computed by the pressure solver is
+                // noisy, so we use its kernel-weighted average on the neighborhood of the particle.
+                let pressure = if total_weight.is_zero() {
+                    N::zero()
+                } else {
+                    weighted_pressure / total_weight
+                };
+                let yield_stress = (k + _3 * alpha * pressure.max(N::zero())).max(N::zero());
+                let sqrt_j2 = (stress.norm_squared() / _2).sqrt();
+
+                if sqrt_j2 > yield_stress {
+                    *stress *= yield_stress / sqrt_j2;
+                }
+            })
+    }
+}
+
+impl<N: RealField> NonPressureForce<N> for DruckerPragerGranular<N> {
+    fn solve(
+        &mut self,
+        _counters: &mut Counters,
+        timestep: &TimestepManager<N>,
+        _kernel_radius: N,
+        fluid_fluid_contacts: &ParticlesContacts<N>,
+        fluid_boundaries_contacts: &ParticlesContacts<N>,
+        fluid: &mut Fluid<N>,
+        boundaries: &[Boundary<N>],
+        densities: &[N],
+    ) {
+        self.stresses.resize(fluid.num_particles(), Matrix::zeros());
+        self.update_stresses(
+            timestep,
+            fluid_fluid_contacts,
+            fluid_boundaries_contacts,
+            fluid,
+            boundaries,
+            densities,
+        );
+
+        let _2: N = na::convert(2.0);
+        let stresses = &self.stresses;
+        let particle_masses: Vec<_> = (0..fluid.num_particles())
+            .map(|i| fluid.particle_mass(i))
+            .collect();
+
+        par_iter_mut!(fluid.accelerations)
+            .enumerate()
+            .for_each(|(i, acceleration)| {
+                let stress_i = stresses[i] / (densities[i] * densities[i]);
+
+                for c in fluid_fluid_contacts
+                    .particle_contacts(i)
+                    .read()
+                    .unwrap()
+                    .iter()
+                {
+                    if c.i_model == c.j_model {
+                        let stress_j = stresses[c.j] / (densities[c.j] * densities[c.j]);
+                        *acceleration += (stress_i + stress_j) * c.gradient * particle_masses[c.j];
+                    }
+                }
+
+                for c in fluid_boundaries_contacts
+                    .particle_contacts(i)
+                    .read()
+                    .unwrap()
+                    .iter()
+                {
+                    let boundary = &boundaries[c.j_model];
+                    let acc = stresses[i]
+                        * c.gradient
+                        * (_2 * boundary.volumes[c.j] * boundary.friction / densities[i]);
+                    *acceleration += acc;
+                    boundary.apply_force(c.j, acc * -particle_masses[i]);
+                }
+            })
+    }
+
+    fn apply_permutation(&mut self, permutation: &[usize]) {
+        if self.stresses.len() == permutation.len() {
+            self.stresses = crate::z_order::apply_permutation(permutation, &self.stresses);
+        }
+    }
+
+    fn apply_particles_removal(&mut self, deleted_particles: &[bool]) {
+        // The particles added since the last step are at the end of the mask, and have no stress yet.
+        let deleted_particles = &deleted_particles[..self.stresses.len()];
+        crate::helper::filter_from_mask(deleted_particles, &mut self.stresses);
+    }
+}
@@ -0,0 +1,3 @@
+pub use self::drucker_prager_granular::DruckerPragerGranular;
+
+mod drucker_prager_granular;
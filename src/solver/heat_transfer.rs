@@ -0,0 +1,207 @@
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+use na::{self, RealField};
+
+use crate::geometry::ContactManager;
+use crate::math::Vector;
+use crate::object::{Boundary, BoundaryTemperature, Fluid};
+use crate::TimestepManager;
+
+// Harmonic mean of two thermal conductivities.
+fn mean_conductivity<N: RealField>(k1: N, k2: N) -> N {
+    if (k1 + k2).is_zero() {
+        N::zero()
+    } else {
+        k1 * k2 * na::convert(2.0) / (k1 + k2)
+    }
+}
+
+// The heat power transmitted from a particle `i` to a particle `j`, according to Cleary et al. 1999.
+fn heat_flux<N: RealField>(
+    conductivity: N,
+    volume_i: N,
+    volume_j: N,
+    t_i: N,
+    t_j: N,
+    x_ij: &Vector<N>,
+    gradient: &Vector<N>,
+    kernel_radius: N,
+) -> N {
+    let eta2 = kernel_radius * kernel_radius * na::convert(0.01);
+    // NOTE: the dot product is negative so this is positive when `t_i > t_j`.
+    -conductivity * volume_i * volume_j * (t_i - t_j) * na::convert(2.0) * x_ij.dot(gradient)
+        / (x_ij.norm_squared() + eta2)
+}
+
+/// Heat conduction between fluid particles, and between fluid and boundary particles.
+pub(crate) struct HeatTransfer<N: RealField> {
+    temperature_changes: Vec<Vec<N>>,
+}
+
+impl<N: RealField> HeatTransfer<N> {
+    pub fn new() -> Self {
+        Self {
+            temperature_changes: Vec::new(),
+        }
+    }
+
+    /// Updates the temperatures of the fluids and boundaries after a substep.
+    pub fn transfer_heat(
+        &mut self,
+        timestep: &TimestepManager<N>,
+        kernel_radius: N,
+        contact_manager: &ContactManager<N>,
+        fluids: &mut [Fluid<N>],
+        boundaries: &mut [Boundary<N>],
+        densities: &[Vec<N>],
+    ) {
+        if fluids.iter().all(|f| f.thermal_conductivity.is_zero()) {
+            return;
+        }
+
+        let dt = timestep.dt();
+        self.temperature_changes.resize(fluids.len(), Vec::new());
+
+        {
+            let fluids = &*fluids;
+            let boundaries = &*boundaries;
+
+            for (fluid_id, (fluid, changes)) in fluids
+                .iter()
+                .zip(self.temperature_changes.iter_mut())
+                .enumerate()
+            {
+                changes.resize(fluid.num_particles(), N::zero());
+
+                let fluid_fluid_contacts = &contact_manager.fluid_fluid_contacts[fluid_id];
+                let fluid_boundary_contacts = &contact_manager.fluid_boundary_contacts[fluid_id];
+                let fluid_densities = &densities[fluid_id];
+
+                par_iter_mut!(changes)
+                    .enumerate()
+                    .for_each(|(i, change_i)| {
+                        let t_i = fluid.temperatures[i];
+                        let v_i = fluid.particle_mass(i) / fluid_densities[i];
+                        let mut power = N::zero();
+
+                        for c in fluid_fluid_contacts
+                            .particle_contacts(i)
+                            .read()
+                            .unwrap()
+                            .iter()
+                        {
+                            let fluid_j = &fluids[c.j_model];
+                            let k = mean_conductivity(
+                                fluid.thermal_conductivity,
+                                fluid_j.thermal_conductivity,
+                            );
+
+                            if !k.is_zero() {
+                                let v_j = fluid_j.particle_mass(c.j) / densities[c.j_model][c.j];
+                                power -= heat_flux(
+                                    k,
+                                    v_i,
+                                    v_j,
+                                    t_i,
+                                    fluid_j.temperatures[c.j],
+                                    &(fluid.positions[c.i] - fluid_j.positions[c.j]),
+                                    &c.gradient,
+                                    kernel_radius,
+                                );
+                            }
+                        }
+
+                        for c in fluid_boundary_contacts
+                            .particle_contacts(i)
+                            .read()
+                            .unwrap()
+                            .iter()
+                        {
+                            let boundary = &boundaries[c.j_model];
+
+                            if let Some(t_j) = boundary.temperature.temperature() {
+                                let k = mean_conductivity(
+                                    fluid.thermal_conductivity,
+                                    boundary.thermal_conductivity,
+                                );
+
+                                if !k.is_zero() {
+                                    power -= heat_flux(
+                                        k,
+                                        v_i,
+                                        boundary.volumes[c.j],
+                                        t_i,
+                                        t_j,
+                                        &(fluid.positions[c.i] - boundary.positions[c.j]),
+                                        &c.gradient,
+                                        kernel_radius,
+                                    );
+                                }
+                            }
+                        }
+
+                        let heat_capacity = fluid.particle_mass(i) * fluid.specific_heat_capacity;
+                        *change_i = if heat_capacity.is_zero() {
+                            N::zero()
+                        } else {
+                            power * dt / heat_capacity
+                        };
+                    })
+            }
+        }
+
+        // Heat received by the conducted boundaries.
+        let mut boundary_powers = vec![N::zero(); boundaries.len()];
+
+        for (fluid_id, fluid) in fluids.iter().enumerate() {
+            let densities = &densities[fluid_id];
+
+            for contacts in contact_manager.fluid_boundary_contacts[fluid_id].contacts() {
+                for c in contacts.read().unwrap().iter() {
+                    let boundary = &boundaries[c.j_model];
+
+                    if let BoundaryTemperature::Conducted { temperature, .. } = boundary.temperature
+                    {
+                        let k = mean_conductivity(
+                            fluid.thermal_conductivity,
+                            boundary.thermal_conductivity,
+                        );
+
+                        if !k.is_zero() {
+                            let power = heat_flux(
+                                k,
+                                fluid.particle_mass(c.i) / densities[c.i],
+                                boundary.volumes[c.j],
+                                fluid.temperatures[c.i],
+                                temperature,
+                                &(fluid.positions[c.i] - boundary.positions[c.j]),
+                                &c.gradient,
+                                kernel_radius,
+                            );
+                            boundary_powers[c.j_model] += power;
+                        }
+                    }
+                }
+            }
+        }
+
+        for (boundary, power) in boundaries.iter_mut().zip(boundary_powers.iter()) {
+            if let BoundaryTemperature::Conducted {
+                temperature,
+                heat_capacity,
+            } = &mut boundary.temperature
+            {
+                if !heat_capacity.is_zero() {
+                    *temperature += *power * dt / *heat_capacity;
+                }
+            }
+        }
+
+        for (fluid, changes) in fluids.iter_mut().zip(self.temperature_changes.iter()) {
+            for (t, change) in fluid.temperatures.iter_mut().zip(changes.iter()) {
+                *t += *change;
+            }
+        }
+    }
+}
@@ -1,14 +1,27 @@
 //! Algorithms for solving pressure, viscosity, surface tension, etc.
 
+pub use self::diffuse::*;
 pub use self::elasticity::*;
+pub use self::granular::*;
+pub use self::multiphase::*;
+pub use self::multiphase_force::MultiphaseForce;
 pub use self::nonpressure_force::NonPressureForce;
 pub use self::pressure::*;
 pub use self::surface_tension::*;
+pub use self::temperature_curve::TemperatureCurve;
 pub use self::viscosity::*;
+pub use self::vorticity::*;
 
+mod diffuse;
 mod elasticity;
+mod granular;
+pub(crate) mod heat_transfer;
 pub(crate) mod helper;
+mod multiphase;
+mod multiphase_force;
 mod nonpressure_force;
 mod pressure;
 mod surface_tension;
+mod temperature_curve;
 mod viscosity;
+mod vorticity;
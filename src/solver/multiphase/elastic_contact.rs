@@ -0,0 +1,254 @@
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+use na::{self, RealField};
+
+use crate::counters::Counters;
+use crate::geometry::ContactManager;
+use crate::math::Vector;
+use crate::object::{Boundary, Fluid};
+use crate::solver::MultiphaseForce;
+use crate::TimestepManager;
+
+/// Penalty-based contact between the particles of distinct elastic fluids.
+///
+/// The pressure solver treats distinct elastic fluids like liquids, which results in soft, inelastic
+/// collisions. This adds a spring-damper force between each pair of particles from two distinct enabled
+/// fluids closer than the sum of their radii, with a damping chosen so each particle pair rebounds with the
+/// given coefficient of restitution. The tangential relative velocity is resisted by a Coulomb friction force.
+///
+/// The fluids are identified by their index in `LiquidWorld::fluids().as_slice()`, and no fluid is enabled
+/// by default. Removing a fluid from the liquid world moves the last fluid to the index of the removed one,
+/// so the enabled indices must then be updated accordingly. Contacts between particles of a same fluid are
+/// left to its elasticity model.
+///
+/// The spring-damper force is integrated implicitly using the length of the previous substep, so it stays
+/// stable for any stiffness. A stiffness much larger than the reduced mass of the particles divided by the
+/// squared length of the substep behaves like a hard non-penetration constraint.
+pub struct ElasticContact<N: RealField> {
+    /// The stiffness of the spring between two overlapping particles, i.e., the repulsive force per unit of overlap.
+    pub stiffness: N,
+    /// The Coulomb friction coefficient between the particles.
+    pub friction: N,
+    /// The coefficient of restitution between the particles, between `0.0` (inelastic) and `1.0` (perfectly elastic).
+    pub restitution: N,
+    enabled_fluids: Vec<bool>,
+    accelerations: Vec<Vec<Vector<N>>>,
+    dt: N,
+}
+
+impl<N: RealField> ElasticContact<N> {
+    /// Initializes an elastic contact model with the given stiffness, friction coefficient, and restitution coefficient.
+    pub fn new(stiffness: N, friction: N, restitution: N) -> Self {
+        Self {
+            stiffness,
+            friction,
+            restitution,
+            enabled_fluids: Vec::new(),
+            accelerations: Vec::new(),
+            dt: N::zero(),
+        }
+    }
+
+    /// Whether the `fluid`-th fluid is subject to elastic contacts with the other enabled fluids.
+    pub fn is_fluid_enabled(&self, fluid: usize) -> bool {
+        self.enabled_fluids.get(fluid).cloned().unwrap_or(false)
+    }
+
+    /// Enables or disables the elastic contacts between the `fluid`-th fluid and the other enabled fluids.
+    pub fn set_fluid_enabled(&mut self, fluid: usize, enabled: bool) {
+        if self.enabled_fluids.len() <= fluid {
+            self.enabled_fluids.resize(fluid + 1, false);
+        }
+
+        self.enabled_fluids[fluid] = enabled;
+    }
+
+    // The damping ratio of a spring-damper with the coefficient of restitution `self.restitution`.
+    fn damping_ratio(&self) -> N {
+        let restitution = self.restitution.max(N::zero()).min(N::one());
+
+        if restitution.is_zero() {
+            return N::one();
+        }
+
+        let log_restitution = restitution.ln();
+        -log_restitution / (N::pi() * N::pi() + log_restitution * log_restitution).sqrt()
+    }
+
+    fn compute_accelerations(&mut self, contact_manager: &ContactManager<N>, fluids: &[Fluid<N>]) {
+        let enabled_fluids = &self.enabled_fluids;
+        let stiffness = self.stiffness;
+        let friction = self.friction;
+        let damping_ratio = self.damping_ratio();
+        let dt = self.dt;
+        let _2: N = na::convert(2.0);
+        self.accelerations.resize(fluids.len(), Vec::new());
+
+        for (fluid_id, (fluid, accelerations)) in
+            fluids.iter().zip(self.accelerations.iter_mut()).enumerate()
+        {
+            accelerations.resize(fluid.num_particles(), Vector::zeros());
+
+            if !enabled_fluids.get(fluid_id).cloned().unwrap_or(false) {
+                accelerations.iter_mut().for_each(|a| a.fill(N::zero()));
+                continue;
+            }
+
+            let fluid_fluid_contacts = &contact_manager.fluid_fluid_contacts[fluid_id];
+
+            par_iter_mut!(accelerations)
+                .enumerate()
+                .for_each(|(i, acceleration)| {
+                    let mass_i = fluid.particle_mass(i);
+                    let mut force = Vector::zeros();
+
+                    for c in fluid_fluid_contacts
+                        .particle_contacts(i)
+                        .read()
+                        .unwrap()
+                        .iter()
+                    {
+                        if c.j_model == fluid_id
+                            || !enabled_fluids.get(c.j_model).cloned().unwrap_or(false)
+                        {
+                            continue;
+                        }
+
+                        let fluid_j = &fluids[c.j_model];
+                        let x_ij = fluid.positions[i] - fluid_j.positions[c.j];
+                        let distance = x_ij.norm();
+                        let overlap =
+                            fluid.particle_radius() + fluid_j.particle_radius() - distance;
+
+                        if overlap <= N::zero() || distance.is_zero() {
+                            continue;
+                        }
+
+                        let normal = x_ij / distance;
+                        let v_ij = fluid.velocities[i] - fluid_j.velocities[c.j];
+                        let normal_velocity = v_ij.dot(&normal);
+                        let tangent_velocity = v_ij - normal * normal_velocity;
+
+                        let mass_j = fluid_j.particle_mass(c.j);
+                        let reduced_mass = mass_i * mass_j / (mass_i + mass_j);
+                        let damping = _2 * damping_ratio * (stiffness * reduced_mass).sqrt();
+
+                        // Backward Euler on the relative motion of the particles, which
+                        // stays stable whatever the stiffness and the damping.
+                        let implicit_factor =
+                            N::one() + (damping * dt + stiffness * dt * dt) / reduced_mass;
+
+                        // No adhesion: the normal force is only repulsive.
+                        let normal_force = ((stiffness * overlap - damping * normal_velocity)
+                            / implicit_factor)
+                            .max(N::zero());
+                        force += normal * normal_force;
+
+                        let tangent_speed = tangent_velocity.norm();
+
+                        if !tangent_speed.is_zero() {
+                            let mut friction_force = friction * normal_force;
+
+                            // Friction must not do more than stopping the tangential motion.
+                            if !dt.is_zero() {
+                                friction_force =
+                                    friction_force.min(reduced_mass * tangent_speed / dt);
+                            }
+
+                            force -= tangent_velocity * (friction_force / tangent_speed);
+                        }
+                    }
+
+                    *acceleration = force / mass_i;
+                })
+        }
+    }
+}
+
+impl<N: RealField> MultiphaseForce<N> for ElasticContact<N> {
+    fn solve(
+        &mut self,
+        _counters: &mut Counters,
+        _kernel_radius: N,
+        contact_manager: &ContactManager<N>,
+        fluids: &mut [Fluid<N>],
+        _boundaries: &[Boundary<N>],
+        _densities: &[Vec<N>],
+    ) {
+        if self.enabled_fluids.iter().filter(|e| **e).count() < 2 {
+            return;
+        }
+
+        self.compute_accelerations(contact_manager, fluids);
+
+        for (fluid, accelerations) in fluids.iter_mut().zip(self.accelerations.iter()) {
+            par_iter_mut!(fluid.accelerations)
+                .zip(par_iter!(accelerations))
+                .for_each(|(acceleration, added_acc)| *acceleration += *added_acc)
+        }
+    }
+
+    fn update(
+        &mut self,
+        _counters: &mut Counters,
+        timestep: &TimestepManager<N>,
+        _kernel_radius: N,
+        _contact_manager: &ContactManager<N>,
+        _fluids: &mut [Fluid<N>],
+        _boundaries: &[Boundary<N>],
+        _densities: &[Vec<N>],
+    ) {
+        // The length of the next substep is not known when the forces are computed,
+        // so the spring-damper is integrated using the length of the last one.
+        self.dt = timestep.dt();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ElasticContact;
+    use crate::math::{Point, Vector};
+    use crate::object::Fluid;
+    use crate::solver::DFSPHSolver;
+    use crate::LiquidWorld;
+
+    // The relative velocity of two particles from distinct fluids after a head-on collision at 2m/s.
+    //
+    // Two isolated particles are never compressed, so they only interact through the contact model.
+    fn rebound_speed(restitution: f64) -> f64 {
+        let particle_radius = 0.025;
+        let mut world = LiquidWorld::new(DFSPHSolver::<f64>::new(), particle_radius, 2.0);
+        let mut contact = ElasticContact::new(1.0e4, 0.0, restitution);
+
+        for (fluid, &(x, speed)) in [(-0.05, 1.0), (0.05, -1.0)].iter().enumerate() {
+            let mut particle =
+                Fluid::new(vec![Point::from(Vector::x() * x)], particle_radius, 1000.0);
+            particle.velocities[0] = Vector::x() * speed;
+            let _ = world.add_fluid(particle);
+            contact.set_fluid_enabled(fluid, true);
+        }
+
+        world.add_multiphase_force(contact);
+
+        for _ in 0..200 {
+            world.step(1.0 / 2000.0, &Vector::zeros());
+        }
+
+        let fluids = world.fluids().as_slice();
+        (fluids[1].velocities[0] - fluids[0].velocities[0]).x
+    }
+
+    #[test]
+    fn restitution_controls_the_rebound() {
+        let inelastic = rebound_speed(0.0);
+        let half = rebound_speed(0.5);
+        let elastic = rebound_speed(1.0);
+
+        // The contact can't pull the particles back together, so even a critically damped
+        // contact lets them separate slowly.
+        assert!((0.0..0.3).contains(&inelastic), "{}", inelastic);
+        assert!((half - 1.0).abs() < 0.15, "{}", half);
+        assert!((elastic - 2.0).abs() < 0.05, "{}", elastic);
+    }
+}
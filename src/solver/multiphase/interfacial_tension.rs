@@ -0,0 +1,194 @@
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+use na::{self, RealField};
+
+use crate::counters::Counters;
+use crate::geometry::ContactManager;
+use crate::math::{Matrix, Vector, DIM};
+use crate::object::{Boundary, Fluid};
+use crate::solver::MultiphaseForce;
+
+/// Interfacial tension between immiscible fluids, using the interface stress of Hu and Adams 2006.
+///
+/// The tension coefficient between each pair of fluids is given by a symmetric matrix where the
+/// `i`-th row and column correspond to the `i`-th fluid of `LiquidWorld::fluids().as_slice()`.
+/// Fluids are not subject to any interfacial tension by default.
+///
+/// This does not handle the surface tension between a fluid and the air, or a boundary.
+/// Use a `NonPressureForce` like `Akinci2013SurfaceTension` for this.
+pub struct InterfacialTension<N: RealField> {
+    tension_coefficients: Vec<Vec<N>>,
+    stresses: Vec<Vec<Matrix<N>>>,
+    accelerations: Vec<Vec<Vector<N>>>,
+}
+
+impl<N: RealField> InterfacialTension<N> {
+    /// Initializes an interfacial tension with all its tension coefficients set to zero.
+    pub fn new() -> Self {
+        Self {
+            tension_coefficients: Vec::new(),
+            stresses: Vec::new(),
+            accelerations: Vec::new(),
+        }
+    }
+
+    /// The tension coefficient between the `fluid1`-th and the `fluid2`-th fluids.
+    pub fn tension_coefficient(&self, fluid1: usize, fluid2: usize) -> N {
+        self.tension_coefficients
+            .get(fluid1)
+            .and_then(|row| row.get(fluid2))
+            .cloned()
+            .unwrap_or_else(N::zero)
+    }
+
+    /// Sets the tension coefficient between the `fluid1`-th and the `fluid2`-th fluids.
+    pub fn set_tension_coefficient(&mut self, fluid1: usize, fluid2: usize, coefficient: N) {
+        let n = self
+            .tension_coefficients
+            .len()
+            .max(fluid1 + 1)
+            .max(fluid2 + 1);
+        self.tension_coefficients.resize(n, Vec::new());
+        self.tension_coefficients
+            .iter_mut()
+            .for_each(|row| row.resize(n, N::zero()));
+        self.tension_coefficients[fluid1][fluid2] = coefficient;
+        self.tension_coefficients[fluid2][fluid1] = coefficient;
+    }
+
+    fn compute_stresses(
+        &mut self,
+        contact_manager: &ContactManager<N>,
+        fluids: &[Fluid<N>],
+        densities: &[Vec<N>],
+    ) {
+        let tension_coefficients = &self.tension_coefficients;
+        let inv_dim: N = na::convert(1.0 / DIM as f64);
+        self.stresses.resize(fluids.len(), Vec::new());
+
+        for (fluid_id, (fluid, stresses)) in fluids.iter().zip(self.stresses.iter_mut()).enumerate()
+        {
+            stresses.resize(fluid.num_particles(), Matrix::zeros());
+            let fluid_fluid_contacts = &contact_manager.fluid_fluid_contacts[fluid_id];
+            let coefficients = tension_coefficients.get(fluid_id);
+
+            par_iter_mut!(stresses).enumerate().for_each(|(i, stress)| {
+                stress.fill(N::zero());
+
+                let coefficients = match coefficients {
+                    Some(coefficients) => coefficients,
+                    None => return,
+                };
+
+                let volume_i = fluid.particle_mass(i) / densities[fluid_id][i];
+
+                for (other_fluid, coefficient) in coefficients.iter().enumerate() {
+                    if other_fluid == fluid_id || coefficient.is_zero() {
+                        continue;
+                    }
+
+                    // The gradient of the color field of the other fluid.
+                    let mut gradient = Vector::zeros();
+
+                    for c in fluid_fluid_contacts
+                        .particle_contacts(i)
+                        .read()
+                        .unwrap()
+                        .iter()
+                    {
+                        if c.j_model == other_fluid {
+                            let volume_j =
+                                fluids[c.j_model].particle_mass(c.j) / densities[c.j_model][c.j];
+                            gradient += c.gradient
+                                * ((volume_i * volume_i + volume_j * volume_j) / volume_i);
+                        }
+                    }
+
+                    let norm = gradient.norm();
+
+                    if !norm.is_zero() {
+                        *stress += (Matrix::identity() * (norm * inv_dim)
+                            - gradient * gradient.transpose() / norm)
+                            * *coefficient;
+                    }
+                }
+            })
+        }
+    }
+
+    fn compute_accelerations(
+        &mut self,
+        contact_manager: &ContactManager<N>,
+        fluids: &[Fluid<N>],
+        densities: &[Vec<N>],
+    ) {
+        let stresses = &self.stresses;
+        self.accelerations.resize(fluids.len(), Vec::new());
+
+        for (fluid_id, (fluid, accelerations)) in
+            fluids.iter().zip(self.accelerations.iter_mut()).enumerate()
+        {
+            accelerations.resize(fluid.num_particles(), Vector::zeros());
+            let fluid_fluid_contacts = &contact_manager.fluid_fluid_contacts[fluid_id];
+
+            par_iter_mut!(accelerations)
+                .enumerate()
+                .for_each(|(i, acceleration)| {
+                    let mass_i = fluid.particle_mass(i);
+                    let volume_i = mass_i / densities[fluid_id][i];
+                    let stress_i = stresses[fluid_id][i] * (volume_i * volume_i);
+                    let mut force = Vector::zeros();
+
+                    for c in fluid_fluid_contacts
+                        .particle_contacts(i)
+                        .read()
+                        .unwrap()
+                        .iter()
+                    {
+                        let volume_j =
+                            fluids[c.j_model].particle_mass(c.j) / densities[c.j_model][c.j];
+                        let stress_j = stresses[c.j_model][c.j] * (volume_j * volume_j);
+                        force += (stress_i + stress_j) * c.gradient;
+                    }
+
+                    *acceleration = force / mass_i;
+                })
+        }
+    }
+}
+
+impl<N: RealField> Default for InterfacialTension<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<N: RealField> MultiphaseForce<N> for InterfacialTension<N> {
+    fn solve(
+        &mut self,
+        _counters: &mut Counters,
+        _kernel_radius: N,
+        contact_manager: &ContactManager<N>,
+        fluids: &mut [Fluid<N>],
+        _boundaries: &[Boundary<N>],
+        densities: &[Vec<N>],
+    ) {
+        if self
+            .tension_coefficients
+            .iter()
+            .all(|row| row.iter().all(|c| c.is_zero()))
+        {
+            return;
+        }
+
+        self.compute_stresses(contact_manager, fluids, densities);
+        self.compute_accelerations(contact_manager, fluids, densities);
+
+        for (fluid, accelerations) in fluids.iter_mut().zip(self.accelerations.iter()) {
+            par_iter_mut!(fluid.accelerations)
+                .zip(par_iter!(accelerations))
+                .for_each(|(acceleration, added_acc)| *acceleration += *added_acc)
+        }
+    }
+}
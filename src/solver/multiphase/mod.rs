@@ -0,0 +1,7 @@
+pub use self::elastic_contact::ElasticContact;
+pub use self::interfacial_tension::InterfacialTension;
+pub use self::ren2014_multiphase::{FluidPhase, Ren2014Multiphase};
+
+mod elastic_contact;
+mod interfacial_tension;
+mod ren2014_multiphase;
@@ -0,0 +1,301 @@
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+use na::{self, RealField};
+
+use crate::counters::Counters;
+use crate::geometry::ContactManager;
+use crate::math::{Vector, DIM};
+use crate::object::{Boundary, Fluid};
+use crate::solver::MultiphaseForce;
+use crate::TimestepManager;
+
+#[derive(Copy, Clone, Debug)]
+/// A phase of a fluid mixture.
+pub struct FluidPhase<N: RealField> {
+    /// The rest density of this phase.
+    pub density0: N,
+    /// The kinematic viscosity of this phase.
+    pub viscosity: N,
+}
+
+impl<N: RealField> FluidPhase<N> {
+    /// Initializes a phase with the given rest density and kinematic viscosity.
+    pub fn new(density0: N, viscosity: N) -> Self {
+        Self {
+            density0,
+            viscosity,
+        }
+    }
+}
+
+fn is_mixture<N: RealField>(fluid: &Fluid<N>) -> bool {
+    !fluid.volume_fractions0.is_empty()
+}
+
+fn mixture_density<N: RealField>(phases: &[FluidPhase<N>], volume_fractions: &[N]) -> N {
+    phases
+        .iter()
+        .zip(volume_fractions.iter())
+        .fold(N::zero(), |acc, (phase, alpha)| {
+            acc + phase.density0 * *alpha
+        })
+}
+
+fn mixture_viscosity<N: RealField>(phases: &[FluidPhase<N>], volume_fractions: &[N]) -> N {
+    phases
+        .iter()
+        .zip(volume_fractions.iter())
+        .fold(N::zero(), |acc, (phase, alpha)| {
+            acc + phase.viscosity * *alpha
+        })
+}
+
+fn update_rest_densities<N: RealField>(phases: &[FluidPhase<N>], fluid: &mut Fluid<N>) {
+    let volume_fractions = &fluid.volume_fractions;
+
+    assert!(
+        volume_fractions.iter().all(|f| f.len() == phases.len()),
+        "The volume fractions of every particle must contain one element per phase."
+    );
+
+    fluid
+        .particle_densities0
+        .resize(fluid.volume_fractions.len(), N::zero());
+
+    par_iter_mut!(fluid.particle_densities0)
+        .enumerate()
+        .for_each(|(i, density0)| *density0 = mixture_density(phases, &volume_fractions[i]))
+}
+
+/// Miscible multiphase model introduced by Ren et al. 2014.
+///
+/// Each particle of the fluids simulated with this model carries the volume fraction of every phase
+/// (see `Fluid::set_volume_fractions`). The volume fractions diffuse across the contacts between the
+/// particles of all these fluids, and the rest density and viscosity of each particle are those of
+/// its mixture of phases. Fluids with empty volume fractions are ignored by this model.
+///
+/// The drift velocities of the original paper are not modeled, i.e., the phases of a mixture do not
+/// separate by themselves.
+pub struct Ren2014Multiphase<N: RealField> {
+    /// The phases of the mixture.
+    pub phases: Vec<FluidPhase<N>>,
+    /// The diffusion coefficient of the volume fractions.
+    ///
+    /// The phases don't mix if this is zero.
+    pub diffusion_coefficient: N,
+    accelerations: Vec<Vec<Vector<N>>>,
+    new_volume_fractions: Vec<Vec<Vec<N>>>,
+}
+
+impl<N: RealField> Ren2014Multiphase<N> {
+    /// Initializes a multiphase model with the given phases and diffusion coefficient.
+    pub fn new(phases: Vec<FluidPhase<N>>, diffusion_coefficient: N) -> Self {
+        Self {
+            phases,
+            diffusion_coefficient,
+            accelerations: Vec::new(),
+            new_volume_fractions: Vec::new(),
+        }
+    }
+
+    fn compute_accelerations(
+        &mut self,
+        kernel_radius: N,
+        contact_manager: &ContactManager<N>,
+        fluids: &[Fluid<N>],
+        densities: &[Vec<N>],
+    ) {
+        let phases = &self.phases;
+        let eta2 = kernel_radius * kernel_radius * na::convert(0.01);
+        let d_plus_2: N = na::convert((DIM + 2) as f64);
+        self.accelerations.resize(fluids.len(), Vec::new());
+
+        for (fluid_id, (fluid, accelerations)) in
+            fluids.iter().zip(self.accelerations.iter_mut()).enumerate()
+        {
+            accelerations.clear();
+
+            if !is_mixture(fluid) {
+                continue;
+            }
+
+            accelerations.resize(fluid.num_particles(), Vector::zeros());
+            let fluid_fluid_contacts = &contact_manager.fluid_fluid_contacts[fluid_id];
+
+            par_iter_mut!(accelerations)
+                .enumerate()
+                .for_each(|(i, acceleration)| {
+                    let viscosity_i = mixture_viscosity(phases, &fluid.volume_fractions[i]);
+                    let mut added_acc = Vector::zeros();
+
+                    for c in fluid_fluid_contacts
+                        .particle_contacts(i)
+                        .read()
+                        .unwrap()
+                        .iter()
+                    {
+                        let fluid_j = &fluids[c.j_model];
+
+                        if !is_mixture(fluid_j) {
+                            continue;
+                        }
+
+                        let viscosity_j = mixture_viscosity(phases, &fluid_j.volume_fractions[c.j]);
+                        let viscosity = (viscosity_i + viscosity_j) * na::convert(0.5);
+                        let volume_j = fluid_j.particle_mass(c.j) / densities[c.j_model][c.j];
+                        let x_ij = fluid.positions[c.i] - fluid_j.positions[c.j];
+                        let v_ij = fluid.velocities[c.i] - fluid_j.velocities[c.j];
+
+                        added_acc += c.gradient
+                            * (d_plus_2
+                                * na::convert(2.0)
+                                * viscosity
+                                * volume_j
+                                * x_ij.dot(&v_ij)
+                                / (x_ij.norm_squared() + eta2));
+                    }
+
+                    *acceleration = added_acc;
+                })
+        }
+    }
+
+    fn diffuse_volume_fractions(
+        &mut self,
+        timestep: &TimestepManager<N>,
+        kernel_radius: N,
+        contact_manager: &ContactManager<N>,
+        fluids: &[Fluid<N>],
+        densities: &[Vec<N>],
+    ) {
+        let diffusion_coefficient = self.diffusion_coefficient;
+        let eta2 = kernel_radius * kernel_radius * na::convert(0.01);
+        let dt = timestep.dt();
+        self.new_volume_fractions.resize(fluids.len(), Vec::new());
+
+        for (fluid_id, (fluid, new_volume_fractions)) in fluids
+            .iter()
+            .zip(self.new_volume_fractions.iter_mut())
+            .enumerate()
+        {
+            new_volume_fractions.clear();
+
+            if !is_mixture(fluid) {
+                continue;
+            }
+
+            new_volume_fractions.resize(fluid.num_particles(), Vec::new());
+            let fluid_fluid_contacts = &contact_manager.fluid_fluid_contacts[fluid_id];
+
+            par_iter_mut!(new_volume_fractions)
+                .enumerate()
+                .for_each(|(i, new_fractions)| {
+                    let fractions = &fluid.volume_fractions[i];
+                    new_fractions.clear();
+                    new_fractions.extend_from_slice(fractions);
+
+                    for c in fluid_fluid_contacts
+                        .particle_contacts(i)
+                        .read()
+                        .unwrap()
+                        .iter()
+                    {
+                        let fluid_j = &fluids[c.j_model];
+
+                        if !is_mixture(fluid_j) {
+                            continue;
+                        }
+
+                        let volume_j = fluid_j.particle_mass(c.j) / densities[c.j_model][c.j];
+                        let x_ij = fluid.positions[c.i] - fluid_j.positions[c.j];
+                        // NOTE: the dot product is negative so the fractions of `i` decrease
+                        // when they are greater than the fractions of `j`.
+                        let coeff = diffusion_coefficient
+                            * dt
+                            * volume_j
+                            * na::convert(2.0)
+                            * x_ij.dot(&c.gradient)
+                            / (x_ij.norm_squared() + eta2);
+
+                        for (new_fraction, (fraction_i, fraction_j)) in new_fractions
+                            .iter_mut()
+                            .zip(fractions.iter().zip(fluid_j.volume_fractions[c.j].iter()))
+                        {
+                            *new_fraction += coeff * (*fraction_i - *fraction_j);
+                        }
+                    }
+
+                    // Clamp negative fractions and renormalize so the fractions sum up to 1.
+                    let mut sum = N::zero();
+
+                    for fraction in new_fractions.iter_mut() {
+                        *fraction = fraction.max(N::zero());
+                        sum += *fraction;
+                    }
+
+                    if !sum.is_zero() {
+                        new_fractions.iter_mut().for_each(|f| *f /= sum);
+                    }
+                })
+        }
+    }
+}
+
+impl<N: RealField> MultiphaseForce<N> for Ren2014Multiphase<N> {
+    fn solve(
+        &mut self,
+        _counters: &mut Counters,
+        kernel_radius: N,
+        contact_manager: &ContactManager<N>,
+        fluids: &mut [Fluid<N>],
+        _boundaries: &[Boundary<N>],
+        densities: &[Vec<N>],
+    ) {
+        for fluid in fluids.iter_mut() {
+            if is_mixture(fluid) {
+                update_rest_densities(&self.phases, fluid);
+            }
+        }
+
+        if self.phases.iter().all(|phase| phase.viscosity.is_zero()) {
+            return;
+        }
+
+        self.compute_accelerations(kernel_radius, contact_manager, fluids, densities);
+
+        for (fluid, accelerations) in fluids.iter_mut().zip(self.accelerations.iter()) {
+            if !accelerations.is_empty() {
+                par_iter_mut!(fluid.accelerations)
+                    .zip(par_iter!(accelerations))
+                    .for_each(|(acceleration, added_acc)| *acceleration += *added_acc)
+            }
+        }
+    }
+
+    fn update(
+        &mut self,
+        _counters: &mut Counters,
+        timestep: &TimestepManager<N>,
+        kernel_radius: N,
+        contact_manager: &ContactManager<N>,
+        fluids: &mut [Fluid<N>],
+        _boundaries: &[Boundary<N>],
+        densities: &[Vec<N>],
+    ) {
+        if self.diffusion_coefficient.is_zero() {
+            return;
+        }
+
+        self.diffuse_volume_fractions(timestep, kernel_radius, contact_manager, fluids, densities);
+
+        for (fluid, new_volume_fractions) in
+            fluids.iter_mut().zip(self.new_volume_fractions.iter_mut())
+        {
+            if !new_volume_fractions.is_empty() {
+                std::mem::swap(&mut fluid.volume_fractions, new_volume_fractions);
+                update_rest_densities(&self.phases, fluid);
+            }
+        }
+    }
+}
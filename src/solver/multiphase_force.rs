@@ -0,0 +1,46 @@
+use crate::counters::Counters;
+use crate::geometry::ContactManager;
+use crate::object::{Boundary, Fluid};
+use crate::TimestepManager;
+use na::RealField;
+
+/// Trait implemented by forces and models coupling several fluids together.
+///
+/// Contrary to a `NonPressureForce` which only sees the fluid it is attached to, a multiphase force
+/// is added to the liquid world and has access to all the fluids, as well as to the contacts
+/// between particles of different fluids.
+pub trait MultiphaseForce<N: RealField>: Send + Sync {
+    /// Compute and applies the multiphase forces to the given fluids.
+    ///
+    /// This is called at each substep, before the pressure resolution. The force application should
+    /// result in adding accelerations to the `accelerations` field of the fluids. The length of the
+    /// substep is not known yet at this point.
+    ///
+    /// The `i`-th element of `densities` contains the densities of the particles of the `i`-th fluid.
+    fn solve(
+        &mut self,
+        counters: &mut Counters,
+        kernel_radius: N,
+        contact_manager: &ContactManager<N>,
+        fluids: &mut [Fluid<N>],
+        boundaries: &[Boundary<N>],
+        densities: &[Vec<N>],
+    );
+
+    /// Updates the per-particle quantities of the given fluids after the positions and velocities
+    /// of their particles have been integrated.
+    ///
+    /// This is called at each substep, after the pressure resolution. It can be left empty if this
+    /// multiphase force does not evolve any per-particle quantity with time.
+    fn update(
+        &mut self,
+        _counters: &mut Counters,
+        _timestep: &TimestepManager<N>,
+        _kernel_radius: N,
+        _contact_manager: &ContactManager<N>,
+        _fluids: &mut [Fluid<N>],
+        _boundaries: &[Boundary<N>],
+        _densities: &[Vec<N>],
+    ) {
+    }
+}
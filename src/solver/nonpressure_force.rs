@@ -1,3 +1,4 @@
+use crate::counters::Counters;
 use crate::geometry::ParticlesContacts;
 use crate::object::{Boundary, Fluid};
 use crate::TimestepManager;
@@ -14,6 +15,7 @@ pub trait NonPressureForce<N: RealField>: Send + Sync {
     /// `fluid.accelerations` field.
     fn solve(
         &mut self,
+        counters: &mut Counters,
         timestep: &TimestepManager<N>,
         kernel_radius: N,
         fluid_fluid_contacts: &ParticlesContacts<N>,
@@ -27,4 +29,11 @@ pub trait NonPressureForce<N: RealField>: Send + Sync {
     ///
     /// This is currently not used so it can be left empty.
     fn apply_permutation(&mut self, _permutation: &[usize]) {}
+
+    /// Removes from all the relevant per-particle fields of this non-pressure force the
+    /// particles such that `deleted_particles[i]` is `true`.
+    ///
+    /// This is called right before these particles are removed from the fluid. This can be
+    /// left empty if this non-pressure force does not store any per-particle data across timesteps.
+    fn apply_particles_removal(&mut self, _deleted_particles: &[bool]) {}
 }
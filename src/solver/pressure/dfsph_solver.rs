@@ -10,7 +10,7 @@ use crate::geometry::{ContactManager, ParticlesContacts};
 use crate::kernel::{CubicSplineKernel, Kernel};
 use crate::math::{Vector, DIM};
 use crate::object::{Boundary, Fluid};
-use crate::solver::{helper, PressureSolver};
+use crate::solver::{helper, DensityFormulation, PressureSolver};
 use crate::TimestepManager;
 
 /// A DFSPH (Divergence Free Smoothed Particle Hydrodynamics) pressure solver.
@@ -37,11 +37,16 @@ pub struct DFSPHSolver<
     /// The pressure solver will continue iterating until the divergence error drops bellow this
     /// threshold, or until the maximum number of pressure iterations is reached.
     pub max_divergence_error: N,
+    /// The way the densities of the fluid particles are computed.
+    ///
+    /// Use `DensityFormulation::NumberDensity` for fluids with high density ratios.
+    pub density_formulation: DensityFormulation,
     min_neighbors_for_divergence_solve: usize,
     alphas: Vec<Vec<N>>,
     densities: Vec<Vec<N>>,
     predicted_densities: Vec<Vec<N>>,
     divergences: Vec<Vec<N>>,
+    pressures: Vec<Vec<N>>,
     velocity_changes: Vec<Vec<Vector<N>>>,
     phantoms: PhantomData<(KernelDensity, KernelGradient)>,
 }
@@ -61,11 +66,13 @@ where
             min_divergence_iter: 1,
             max_divergence_iter: 50,
             max_divergence_error: na::convert(0.1),
+            density_formulation: DensityFormulation::Mass,
             min_neighbors_for_divergence_solve: if DIM == 2 { 6 } else { 20 },
             alphas: Vec::new(),
             densities: Vec::new(),
             predicted_densities: Vec::new(),
             divergences: Vec::new(),
+            pressures: Vec::new(),
             velocity_changes: Vec::new(),
             phantoms: PhantomData,
         }
@@ -105,6 +112,7 @@ where
         fluids: &[Fluid<N>],
         boundaries: &[Boundary<N>],
     ) -> N {
+        let formulation = self.density_formulation;
         let velocity_changes = &self.velocity_changes;
         let densities = &self.densities;
         let mut max_error = N::zero();
@@ -126,7 +134,9 @@ where
                         let vi = fluid_i.velocities[c.i] + velocity_changes[c.i_model][c.i];
                         let vj = fluid_j.velocities[c.j] + velocity_changes[c.j_model][c.j];
 
-                        delta += fluids[c.j_model].particle_mass(c.j) * (vi - vj).dot(&c.gradient);
+                        let mj = formulation
+                            .density_mass(fluid_i.particle_mass(c.i), fluid_j.particle_mass(c.j));
+                        delta += mj * (vi - vj).dot(&c.gradient);
                     }
 
                     for c in fluid_boundary_contacts[fluid_id]
@@ -139,17 +149,17 @@ where
                         let vj = boundaries[c.j_model].velocities[c.j];
 
                         delta += boundaries[c.j_model].volumes[c.j]
-                            * fluid_i.density0
+                            * fluid_i.particle_density0(i)
                             * (vi - vj).dot(&c.gradient);
                     }
 
                     *predicted_density = densities[fluid_id][i] + delta * timestep.dt();
                     assert!(!predicted_density.is_zero());
 
-                    if *predicted_density < fluid_i.density0 {
+                    if *predicted_density < fluid_i.particle_density0(i) {
                         N::zero()
                     } else {
-                        *predicted_density / fluid_i.density0 - N::one()
+                        *predicted_density / fluid_i.particle_density0(i) - N::one()
                     }
                 });
             let err = par_reduce_sum!(N::zero(), it);
@@ -171,6 +181,7 @@ where
         fluids: &[Fluid<N>],
         boundaries: &[Boundary<N>],
     ) {
+        let formulation = self.density_formulation;
         for fluid_id in 0..fluids.len() {
             let fluid_fluid_contacts = &fluid_fluid_contacts[fluid_id];
             let fluid_boundary_contacts = &fluid_boundary_contacts[fluid_id];
@@ -189,9 +200,22 @@ where
                         .unwrap()
                         .iter()
                     {
-                        let grad_i = c.gradient * fluids[c.j_model].particle_mass(c.j);
-                        squared_grad_sum += grad_i.norm_squared();
-                        grad_sum += grad_i;
+                        let mi = fluid_i.particle_mass(c.i);
+                        let mj = fluids[c.j_model].particle_mass(c.j);
+                        // The mass of `j` in the density of `i`, and the mass weighting the squared
+                        // gradient. With the `DensityFormulation::Mass`, the latter is `mj` as in the
+                        // original DFSPH. Otherwise, this is the mass of `i` in the pressure
+                        // acceleration of `j`.
+                        let density_mass = formulation.density_mass(mi, mj);
+                        let pressure_mass = match formulation {
+                            DensityFormulation::Mass => mj,
+                            DensityFormulation::NumberDensity => {
+                                formulation.pressure_masses(mj, mi).1
+                            }
+                        };
+                        squared_grad_sum +=
+                            c.gradient.norm_squared() * density_mass * pressure_mass;
+                        grad_sum += c.gradient * density_mass;
                     }
 
                     for c in fluid_boundary_contacts
@@ -200,8 +224,9 @@ where
                         .unwrap()
                         .iter()
                     {
-                        let grad_i =
-                            c.gradient * boundaries[c.j_model].volumes[c.j] * fluid_i.density0;
+                        let grad_i = c.gradient
+                            * boundaries[c.j_model].volumes[c.j]
+                            * fluid_i.particle_density0(i);
                         squared_grad_sum += grad_i.norm_squared();
                         grad_sum += grad_i;
                     }
@@ -225,6 +250,7 @@ where
         fluids: &[Fluid<N>],
         boundaries: &[Boundary<N>],
     ) {
+        let formulation = self.density_formulation;
         let alphas = &self.alphas;
         let predicted_densities = &self.predicted_densities;
 
@@ -233,8 +259,8 @@ where
                 .enumerate()
                 .for_each(|(i, velocity_change)| {
                     let fluid1 = &fluids[fluid_id];
-                    let ki =
-                        (predicted_densities[fluid_id][i] - fluid1.density0) * alphas[fluid_id][i];
+                    let ki = (predicted_densities[fluid_id][i] - fluid1.particle_density0(i))
+                        * alphas[fluid_id][i];
 
                     for c in fluid_fluid_contacts[fluid_id]
                         .particle_contacts(i)
@@ -244,14 +270,16 @@ where
                     {
                         let fluid2 = &fluids[c.j_model];
 
-                        let kj = (predicted_densities[c.j_model][c.j] - fluid2.density0)
+                        let kj = (predicted_densities[c.j_model][c.j]
+                            - fluid2.particle_density0(c.j))
                             * alphas[c.j_model][c.j];
 
-                        let kij = ki.max(N::zero()) + kj.max(N::zero());
+                        let (mi, mj) = formulation
+                            .pressure_masses(fluid1.particle_mass(c.i), fluid2.particle_mass(c.j));
+                        let coeff = ki.max(N::zero()) * mi + kj.max(N::zero()) * mj;
 
                         // Compute velocity change.
-                        if kij > N::zero() {
-                            let coeff = kij * fluid2.particle_mass(c.j);
+                        if coeff > N::zero() {
                             *velocity_change -= c.gradient * (coeff * timestep.inv_dt());
                         }
                     }
@@ -263,7 +291,9 @@ where
                             .unwrap()
                             .iter()
                         {
-                            let coeff = ki * boundaries[c.j_model].volumes[c.j] * fluid1.density0;
+                            let coeff = ki
+                                * boundaries[c.j_model].volumes[c.j]
+                                * fluid1.particle_density0(i);
                             let delta = c.gradient * (coeff * timestep.inv_dt());
 
                             *velocity_change -= delta;
@@ -285,6 +315,7 @@ where
         fluids: &[Fluid<N>],
         boundaries: &[Boundary<N>],
     ) -> N {
+        let formulation = self.density_formulation;
         let velocity_changes = &self.velocity_changes;
         let min_neighbors_for_divergence_solve = self.min_neighbors_for_divergence_solve;
         let mut max_error = N::zero();
@@ -325,7 +356,9 @@ where
                         let v_i = fluid_i.velocities[c.i] + velocity_changes[c.i_model][c.i];
                         let v_j = fluid_j.velocities[c.j] + velocity_changes[c.j_model][c.j];
                         let dvel = v_i - v_j;
-                        *divergence_i += dvel.dot(&c.gradient) * fluid_j.particle_mass(c.j);
+                        let mj = formulation
+                            .density_mass(fluid_i.particle_mass(c.i), fluid_j.particle_mass(c.j));
+                        *divergence_i += dvel.dot(&c.gradient) * mj;
                     }
 
                     for c in fluid_boundary_contacts
@@ -340,11 +373,11 @@ where
                         let dvel = v_i;
                         *divergence_i += dvel.dot(&c.gradient)
                             * boundaries[c.j_model].volumes[c.j]
-                            * fluid_i.density0;
+                            * fluid_i.particle_density0(i);
                     }
 
                     *divergence_i = divergence_i.max(N::zero());
-                    *divergence_i / fluid_i.density0
+                    *divergence_i / fluid_i.particle_density0(i)
                 });
             let err = par_reduce_sum!(N::zero(), it);
 
@@ -365,6 +398,7 @@ where
         fluids: &[Fluid<N>],
         boundaries: &[Boundary<N>],
     ) {
+        let formulation = self.density_formulation;
         let alphas = &self.alphas;
         let divergences = &self.divergences;
 
@@ -385,7 +419,9 @@ where
                         let kj = divergences[c.j_model][c.j] * alphas[c.j_model][c.j];
 
                         // Compute velocity change.
-                        let coeff = -(ki + kj) * fluid2.particle_mass(c.j);
+                        let (mi, mj) = formulation
+                            .pressure_masses(fluid1.particle_mass(c.i), fluid2.particle_mass(c.j));
+                        let coeff = -(ki * mi + kj * mj);
                         *velocity_change += c.gradient * coeff;
                     }
 
@@ -398,7 +434,8 @@ where
                         let boundary2 = &boundaries[c.j_model];
 
                         // Compute velocity change.
-                        let coeff = -ki * boundaries[c.j_model].volumes[c.j] * fluid1.density0;
+                        let coeff =
+                            -ki * boundaries[c.j_model].volumes[c.j] * fluid1.particle_density0(i);
                         let delta = c.gradient * coeff;
                         *velocity_change += delta;
 
@@ -431,6 +468,44 @@ where
         }
     }
 
+    // Accumulates the pressures corresponding to the velocity changes of the current iteration
+    // of the divergence solve or of the pressure solve.
+    fn accumulate_pressures(
+        &mut self,
+        timestep: &TimestepManager<N>,
+        fluids: &[Fluid<N>],
+        divergence_solve: bool,
+    ) {
+        let factor = if divergence_solve {
+            timestep.inv_dt()
+        } else {
+            timestep.inv_dt() * timestep.inv_dt()
+        };
+
+        for (fluid_id, fluid) in fluids.iter().enumerate() {
+            let pressures = &mut self.pressures[fluid_id];
+            let alphas = &self.alphas[fluid_id];
+            let densities = &self.densities[fluid_id];
+            let divergences = &self.divergences[fluid_id];
+            let predicted_densities = &self.predicted_densities[fluid_id];
+
+            par_iter_mut!(pressures)
+                .enumerate()
+                .for_each(|(i, pressure)| {
+                    let ki = if divergence_solve {
+                        divergences[i] * alphas[i]
+                    } else {
+                        (predicted_densities[i] - fluid.particle_density0(i)) * alphas[i]
+                    };
+
+                    // NOTE: `alphas` actually contain `alpha_i / density_i`.
+                    if ki > N::zero() {
+                        *pressure += ki * densities[i] * densities[i] * factor;
+                    }
+                })
+        }
+    }
+
     fn pressure_solve(
         &mut self,
         timestep: &TimestepManager<N>,
@@ -455,6 +530,7 @@ where
                 break;
             }
 
+            self.accumulate_pressures(timestep, fluids, false);
             self.compute_velocity_changes(
                 timestep,
                 &contact_manager.fluid_fluid_contacts,
@@ -492,6 +568,7 @@ where
 
             counters.custom.resume();
 
+            self.accumulate_pressures(timestep, fluids, true);
             self.compute_velocity_changes_for_divergence(
                 timestep,
                 &contact_manager.fluid_fluid_contacts,
@@ -532,22 +609,31 @@ where
         self.densities.resize(fluids.len(), Vec::new());
         self.predicted_densities.resize(fluids.len(), Vec::new());
         self.divergences.resize(fluids.len(), Vec::new());
+        self.pressures.resize(fluids.len(), Vec::new());
         self.velocity_changes.resize(fluids.len(), Vec::new());
 
-        for (fluid, alphas, densities, predicted_densities, divergences, velocity_changes) in
-            itertools::multizip((
-                fluids.iter(),
-                self.alphas.iter_mut(),
-                self.densities.iter_mut(),
-                self.predicted_densities.iter_mut(),
-                self.divergences.iter_mut(),
-                self.velocity_changes.iter_mut(),
-            ))
-        {
+        for (
+            fluid,
+            alphas,
+            densities,
+            predicted_densities,
+            divergences,
+            pressures,
+            velocity_changes,
+        ) in itertools::multizip((
+            fluids.iter(),
+            self.alphas.iter_mut(),
+            self.densities.iter_mut(),
+            self.predicted_densities.iter_mut(),
+            self.divergences.iter_mut(),
+            self.pressures.iter_mut(),
+            self.velocity_changes.iter_mut(),
+        )) {
             alphas.resize(fluid.num_particles(), N::zero());
             densities.resize(fluid.num_particles(), N::zero());
             predicted_densities.resize(fluid.num_particles(), N::zero());
             divergences.resize(fluid.num_particles(), N::zero());
+            pressures.resize(fluid.num_particles(), N::zero());
             velocity_changes.resize(fluid.num_particles(), Vector::zeros());
 
             if fluid.num_deleted_particles() != 0 {
@@ -558,6 +644,7 @@ where
                     predicted_densities,
                 );
                 crate::helper::filter_from_mask(fluid.deleted_particles_mask(), divergences);
+                crate::helper::filter_from_mask(fluid.deleted_particles_mask(), pressures);
                 crate::helper::filter_from_mask(fluid.deleted_particles_mask(), velocity_changes);
             }
         }
@@ -567,6 +654,7 @@ where
 
     fn predict_advection(
         &mut self,
+        counters: &mut Counters,
         timestep: &TimestepManager<N>,
         kernel_radius: N,
         contact_manager: &ContactManager<N>,
@@ -574,6 +662,8 @@ where
         fluids: &mut [Fluid<N>],
         boundaries: &[Boundary<N>],
     ) {
+        counters.solver.non_pressure_resolution_time.resume();
+
         for fluid in fluids.iter_mut() {
             par_iter_mut!(fluid.accelerations).for_each(|acceleration| {
                 *acceleration += gravity;
@@ -592,6 +682,7 @@ where
 
             for np_force in &mut forces {
                 np_force.solve(
+                    counters,
                     timestep,
                     kernel_radius,
                     fluid_fluid_contacts,
@@ -604,6 +695,8 @@ where
 
             fluid.nonpressure_forces = forces;
         }
+
+        counters.solver.non_pressure_resolution_time.pause();
     }
 
     fn evaluate_kernels(
@@ -634,6 +727,7 @@ where
         fluids: &[Fluid<N>],
         boundaries: &mut [Boundary<N>],
     ) {
+        let formulation = self.density_formulation;
         self.compute_boundary_volumes(&contact_manager.boundary_boundary_contacts, boundaries);
 
         for fluid_id in 0..fluids.len() {
@@ -648,7 +742,10 @@ where
                         .unwrap()
                         .iter()
                     {
-                        *density += fluids[c.j_model].particle_mass(c.j) * c.weight;
+                        *density += formulation.density_mass(
+                            fluids[c.i_model].particle_mass(c.i),
+                            fluids[c.j_model].particle_mass(c.j),
+                        ) * c.weight;
                     }
 
                     for c in contact_manager.fluid_boundary_contacts[fluid_id]
@@ -658,7 +755,7 @@ where
                         .iter()
                     {
                         *density += boundaries[c.j_model].volumes[c.j]
-                            * fluids[c.i_model].density0
+                            * fluids[c.i_model].particle_density0(c.i)
                             * c.weight;
                     }
 
@@ -667,6 +764,10 @@ where
         }
     }
 
+    fn densities(&self) -> &[Vec<N>] {
+        &self.densities
+    }
+
     fn step(
         &mut self,
         counters: &mut Counters,
@@ -679,6 +780,10 @@ where
     ) {
         counters.solver.pressure_resolution_time.resume();
 
+        self.pressures
+            .iter_mut()
+            .for_each(|ps| ps.iter_mut().for_each(|p| *p = N::zero()));
+
         self.compute_alphas(
             &contact_manager.fluid_fluid_contacts,
             &contact_manager.fluid_boundary_contacts,
@@ -693,7 +798,9 @@ where
             .iter_mut()
             .for_each(|vs| vs.iter_mut().for_each(|v| v.fill(N::zero())));
 
+        counters.solver.pressure_resolution_time.pause();
         self.predict_advection(
+            counters,
             timestep,
             kernel_radius,
             contact_manager,
@@ -701,12 +808,69 @@ where
             fluids,
             boundaries,
         );
+        counters.solver.pressure_resolution_time.resume();
 
         timestep.advance(fluids);
 
         self.integrate_and_clear_accelerations(timestep, fluids);
         self.pressure_solve(timestep, contact_manager, fluids, boundaries);
         self.update_positions(timestep, fluids);
+
+        for (fluid, pressures) in fluids.iter_mut().zip(self.pressures.iter()) {
+            fluid.pressures.copy_from_slice(pressures);
+        }
         counters.solver.pressure_resolution_time.pause();
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::DFSPHSolver;
+    use crate::geometry::{Contact, ParticlesContacts};
+    use crate::math::{Point, Vector};
+    use crate::object::Fluid;
+    use crate::solver::PressureSolver;
+
+    #[test]
+    fn mass_formulation_alphas_with_unequal_masses() {
+        let mut fluid = Fluid::new(
+            vec![Point::origin(), Point::from(Vector::x() * 0.05)],
+            0.025,
+            1000.0,
+        );
+        fluid.volumes[1] *= 4.0;
+
+        let gradient = Vector::x() * 10.0;
+        let contact = Contact {
+            i: 0,
+            i_model: 0,
+            j: 1,
+            j_model: 0,
+            weight: 1.0,
+            gradient,
+        };
+        let mut contacts = ParticlesContacts::new();
+        contacts.resize(2);
+        contacts.particle_contacts(0).write().unwrap().push(contact);
+        contacts
+            .particle_contacts(1)
+            .write()
+            .unwrap()
+            .push(contact.flip());
+
+        let mut boundary_contacts = ParticlesContacts::new();
+        boundary_contacts.resize(2);
+
+        let fluids = [fluid];
+        let mut solver = DFSPHSolver::<f64>::new();
+        solver.init_with_fluids(&fluids);
+        solver.compute_alphas(&[contacts], &[boundary_contacts], &fluids, &[]);
+
+        // Each alpha only depends on the mass of the neighbor, as in the original DFSPH.
+        for (i, j) in [(0, 1), (1, 0)].iter() {
+            let mj = fluids[0].particle_mass(*j);
+            let expected = 1.0 / (2.0 * mj * mj * gradient.norm_squared());
+            assert!((solver.alphas[0][*i] - expected).abs() <= expected * 1.0e-12);
+        }
+    }
+}
@@ -10,7 +10,7 @@ use crate::geometry::{ContactManager, ParticlesContacts};
 use crate::kernel::{CubicSplineKernel, Kernel};
 use crate::math::Vector;
 use crate::object::{Boundary, Fluid};
-use crate::solver::{helper, PressureSolver};
+use crate::solver::{helper, DensityFormulation, PressureSolver};
 use crate::TimestepManager;
 
 /// A IISPH (Implicit Incompressible Smoothed Particle Hydrodynamics) pressure solver.
@@ -28,6 +28,10 @@ pub struct IISPHSolver<
     /// The pressure solver will continue iterating until the density error drops bellow this
     /// threshold, or until the maximum number of pressure iterations is reached.
     pub max_density_error: N,
+    /// The way the densities of the fluid particles are computed.
+    ///
+    /// Use `DensityFormulation::NumberDensity` for fluids with high density ratios.
+    pub density_formulation: DensityFormulation,
     omega: N,
     densities: Vec<Vec<N>>,
     aii: Vec<Vec<N>>,
@@ -52,6 +56,7 @@ where
             min_pressure_iter: 1,
             max_pressure_iter: 50,
             max_density_error: na::convert(0.05),
+            density_formulation: DensityFormulation::Mass,
             omega: na::convert(0.5),
             densities: Vec::new(),
             dii: Vec::new(),
@@ -99,6 +104,7 @@ where
         fluids: &[Fluid<N>],
         boundaries: &[Boundary<N>],
     ) {
+        let formulation = self.density_formulation;
         let velocity_changes = &self.velocity_changes;
         let densities = &self.densities;
         let _max_error = N::zero();
@@ -120,7 +126,9 @@ where
                         let vi = fluid_i.velocities[c.i] + velocity_changes[c.i_model][c.i];
                         let vj = fluid_j.velocities[c.j] + velocity_changes[c.j_model][c.j];
 
-                        delta += fluids[c.j_model].particle_mass(c.j) * (vi - vj).dot(&c.gradient);
+                        let mj = formulation
+                            .density_mass(fluid_i.particle_mass(c.i), fluid_j.particle_mass(c.j));
+                        delta += mj * (vi - vj).dot(&c.gradient);
                     }
 
                     for c in fluid_boundary_contacts[fluid_id]
@@ -133,7 +141,7 @@ where
                         let vj = boundaries[c.j_model].velocities[c.j];
 
                         delta += boundaries[c.j_model].volumes[c.j]
-                            * fluid_i.density0
+                            * fluid_i.particle_density0(i)
                             * (vi - vj).dot(&c.gradient);
                     }
 
@@ -151,6 +159,7 @@ where
         fluids: &[Fluid<N>],
         boundaries: &[Boundary<N>],
     ) {
+        let formulation = self.density_formulation;
         for fluid_id in 0..fluids.len() {
             let fluid_fluid_contacts = &fluid_fluid_contacts[fluid_id];
             let fluid_boundary_contacts = &fluid_boundary_contacts[fluid_id];
@@ -170,7 +179,10 @@ where
                     .unwrap()
                     .iter()
                 {
-                    let mj = fluids[c.j_model].particle_mass(c.j);
+                    let (mj, _) = formulation.pressure_masses(
+                        fluid_i.particle_mass(c.i),
+                        fluids[c.j_model].particle_mass(c.j),
+                    );
                     *dii += c.gradient * (mj * factor);
                 }
 
@@ -180,7 +192,7 @@ where
                     .unwrap()
                     .iter()
                 {
-                    let mj = boundaries[c.j_model].volumes[c.j] * fluid_i.density0;
+                    let mj = boundaries[c.j_model].volumes[c.j] * fluid_i.particle_density0(i);
                     *dii += c.gradient * (mj * factor);
                 }
             })
@@ -195,6 +207,7 @@ where
         fluids: &[Fluid<N>],
         boundaries: &[Boundary<N>],
     ) {
+        let formulation = self.density_formulation;
         for fluid_id in 0..fluids.len() {
             let fluid_fluid_contacts = &fluid_fluid_contacts[fluid_id];
             let fluid_boundary_contacts = &fluid_boundary_contacts[fluid_id];
@@ -207,7 +220,7 @@ where
                 *aii = N::zero();
                 let rhoi = densities[fluid_id][i];
                 let mi = fluids[fluid_id].particle_mass(i);
-                let factor = timestep.dt() * timestep.dt() * mi / (rhoi * rhoi);
+                let factor = timestep.dt() * timestep.dt() / (rhoi * rhoi);
 
                 for c in fluid_fluid_contacts
                     .particle_contacts(i)
@@ -216,8 +229,9 @@ where
                     .iter()
                 {
                     let mj = fluids[c.j_model].particle_mass(c.j);
-                    let dji = c.gradient * factor;
-                    *aii += mj * (dii[c.i] - dji).dot(&c.gradient);
+                    let (_, mji) = formulation.pressure_masses(mj, mi);
+                    let dji = c.gradient * (factor * mji);
+                    *aii += formulation.density_mass(mi, mj) * (dii[c.i] - dji).dot(&c.gradient);
                 }
 
                 for c in fluid_boundary_contacts
@@ -226,8 +240,8 @@ where
                     .unwrap()
                     .iter()
                 {
-                    let mj = boundaries[c.j_model].volumes[c.j] * fluid_i.density0;
-                    let dji = c.gradient * factor;
+                    let mj = boundaries[c.j_model].volumes[c.j] * fluid_i.particle_density0(i);
+                    let dji = c.gradient * (factor * mi);
                     *aii += mj * (dii[c.i] - dji).dot(&c.gradient);
                 }
             })
@@ -241,11 +255,12 @@ where
         fluid_boundary_contacts: &[ParticlesContacts<N>],
         fluids: &[Fluid<N>],
     ) {
+        let formulation = self.density_formulation;
         for fluid_id in 0..fluids.len() {
             let fluid_fluid_contacts = &fluid_fluid_contacts[fluid_id];
             let _fluid_boundary_contacts = &fluid_boundary_contacts[fluid_id];
             let dij_pjl = &mut self.dij_pjl[fluid_id];
-            let _fluid_i = &fluids[fluid_id];
+            let fluid_i = &fluids[fluid_id];
             let densities = &self.densities;
             let pressures = &self.pressures;
 
@@ -259,7 +274,10 @@ where
                     .iter()
                 {
                     let rhoj = densities[c.j_model][c.j];
-                    let mj = fluids[c.j_model].particle_mass(c.j);
+                    let (_, mj) = formulation.pressure_masses(
+                        fluid_i.particle_mass(c.i),
+                        fluids[c.j_model].particle_mass(c.j),
+                    );
                     let p_jl = pressures[c.j_model][c.j];
                     *dij_pjl += c.gradient * (-mj * p_jl / (rhoj * rhoj));
                 }
@@ -277,6 +295,7 @@ where
         fluids: &[Fluid<N>],
         boundaries: &[Boundary<N>],
     ) -> N {
+        let formulation = self.density_formulation;
         let mut max_error = N::zero();
 
         for fluid_id in 0..fluids.len() {
@@ -300,7 +319,7 @@ where
                         let pi = pressures[fluid_id][i];
                         let mi = fluid_i.particle_mass(i);
                         let rhoi = densities[fluid_id][i];
-                        let derr = fluid_i.density0 - predicted_densities[fluid_id][i];
+                        let derr = fluid_i.particle_density0(i) - predicted_densities[fluid_id][i];
 
                         for c in fluid_fluid_contacts
                             .particle_contacts(i)
@@ -309,12 +328,13 @@ where
                             .iter()
                         {
                             let mj = fluids[c.j_model].particle_mass(c.j);
+                            let (_, mji) = formulation.pressure_masses(mj, mi);
                             let dji =
-                                c.gradient * (timestep.dt() * timestep.dt() * mi / (rhoi * rhoi));
+                                c.gradient * (timestep.dt() * timestep.dt() * mji / (rhoi * rhoi));
                             let factor = dij_pjl[c.i_model][c.i]
                                 - dii[c.j_model][c.j] * pressures[c.j_model][c.j]
                                 - (dij_pjl[c.j_model][c.j] - dji * pi);
-                            sum += mj * factor.dot(&c.gradient);
+                            sum += formulation.density_mass(mi, mj) * factor.dot(&c.gradient);
                         }
 
                         for c in fluid_boundary_contacts
@@ -323,7 +343,8 @@ where
                             .unwrap()
                             .iter()
                         {
-                            let mj = boundaries[c.j_model].volumes[c.j] * fluid_i.density0;
+                            let mj =
+                                boundaries[c.j_model].volumes[c.j] * fluid_i.particle_density0(i);
                             sum += mj * dij_pjl[c.i_model][c.i].dot(&c.gradient);
                         }
 
@@ -331,7 +352,7 @@ where
 
                         if *next_pressure > N::zero() {
                             *next_pressure = next_pressure.max(N::zero());
-                            (-sum - aii[i] * *next_pressure) / fluid_i.density0
+                            (-sum - aii[i] * *next_pressure) / fluid_i.particle_density0(i)
                         } else {
                             // Clamp negative pressures.
                             *next_pressure = N::zero();
@@ -361,6 +382,7 @@ where
         fluids: &[Fluid<N>],
         boundaries: &[Boundary<N>],
     ) {
+        let formulation = self.density_formulation;
         let densities = &self.densities;
         let pressures = &self.pressures;
 
@@ -378,12 +400,15 @@ where
                         .unwrap()
                         .iter()
                     {
-                        let mj = fluids[c.j_model].particle_mass(c.j);
+                        let (mi, mj) = formulation.pressure_masses(
+                            fluid_i.particle_mass(c.i),
+                            fluids[c.j_model].particle_mass(c.j),
+                        );
                         let pj = pressures[c.j_model][c.j];
                         let rhoj = densities[c.j_model][c.j];
 
                         *velocity_change -= c.gradient
-                            * (timestep.dt() * mj * (pi / (rhoi * rhoi) + pj / (rhoj * rhoj)));
+                            * (timestep.dt() * (mi * pi / (rhoi * rhoi) + mj * pj / (rhoj * rhoj)));
                     }
 
                     for c in fluid_boundary_contacts[fluid_id]
@@ -392,7 +417,7 @@ where
                         .unwrap()
                         .iter()
                     {
-                        let mj = boundaries[c.j_model].volumes[c.j] * fluid_i.density0;
+                        let mj = boundaries[c.j_model].volumes[c.j] * fluid_i.particle_density0(i);
                         let acc = c.gradient * (mj * pi / (rhoi * rhoi));
                         *velocity_change -= acc * timestep.dt();
 
@@ -454,6 +479,10 @@ where
                 break;
             }
         }
+
+        for (fluid, pressures) in fluids.iter_mut().zip(self.pressures.iter()) {
+            fluid.pressures.copy_from_slice(pressures);
+        }
     }
 
     fn integrate_and_clear_accelerations(
@@ -519,6 +548,7 @@ where
 
     fn predict_advection(
         &mut self,
+        counters: &mut Counters,
         timestep: &TimestepManager<N>,
         kernel_radius: N,
         contact_manager: &ContactManager<N>,
@@ -526,6 +556,8 @@ where
         fluids: &mut [Fluid<N>],
         boundaries: &[Boundary<N>],
     ) {
+        counters.solver.non_pressure_resolution_time.resume();
+
         for fluid in fluids.iter_mut() {
             par_iter_mut!(fluid.accelerations).for_each(|acceleration| {
                 *acceleration += gravity;
@@ -544,6 +576,7 @@ where
 
             for np_force in &mut forces {
                 np_force.solve(
+                    counters,
                     timestep,
                     kernel_radius,
                     fluid_fluid_contacts,
@@ -556,6 +589,8 @@ where
 
             fluid.nonpressure_forces = forces;
         }
+
+        counters.solver.non_pressure_resolution_time.pause();
     }
 
     fn evaluate_kernels(
@@ -586,6 +621,7 @@ where
         fluids: &[Fluid<N>],
         boundaries: &mut [Boundary<N>],
     ) {
+        let formulation = self.density_formulation;
         self.compute_boundary_volumes(&contact_manager.boundary_boundary_contacts, boundaries);
 
         for fluid_id in 0..fluids.len() {
@@ -600,7 +636,10 @@ where
                         .unwrap()
                         .iter()
                     {
-                        *density += fluids[c.j_model].particle_mass(c.j) * c.weight;
+                        *density += formulation.density_mass(
+                            fluids[c.i_model].particle_mass(c.i),
+                            fluids[c.j_model].particle_mass(c.j),
+                        ) * c.weight;
                     }
 
                     for c in contact_manager.fluid_boundary_contacts[fluid_id]
@@ -610,7 +649,7 @@ where
                         .iter()
                     {
                         *density += boundaries[c.j_model].volumes[c.j]
-                            * fluids[c.i_model].density0
+                            * fluids[c.i_model].particle_density0(c.i)
                             * c.weight;
                     }
 
@@ -619,6 +658,10 @@ where
         }
     }
 
+    fn densities(&self) -> &[Vec<N>] {
+        &self.densities
+    }
+
     fn step(
         &mut self,
         counters: &mut Counters,
@@ -630,6 +673,7 @@ where
         boundaries: &[Boundary<N>],
     ) {
         self.predict_advection(
+            counters,
             timestep,
             kernel_radius,
             contact_manager,
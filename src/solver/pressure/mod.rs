@@ -1,7 +1,9 @@
+pub use self::density_formulation::DensityFormulation;
 pub use self::dfsph_solver::DFSPHSolver;
 pub use self::iisph_solver::IISPHSolver;
 pub use self::pressure_solver::PressureSolver;
 
+mod density_formulation;
 mod dfsph_solver;
 mod iisph_solver;
 mod pressure_solver;
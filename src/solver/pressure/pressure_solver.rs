@@ -17,6 +17,7 @@ pub trait PressureSolver<N: RealField> {
     /// Predicts advection with the given gravity.
     fn predict_advection(
         &mut self,
+        counters: &mut Counters,
         timestep: &TimestepManager<N>,
         kernel_radius: N,
         contact_manager: &ContactManager<N>,
@@ -42,6 +43,11 @@ pub trait PressureSolver<N: RealField> {
         boundaries: &mut [Boundary<N>],
     );
 
+    /// The densities of the fluid particles, as computed by the last call to `self.compute_densities`.
+    ///
+    /// The `i`-th element of this slice contains the densities of all the particles of the `i`-th fluid.
+    fn densities(&self) -> &[Vec<N>];
+
     /// Solves pressure and non-pressure force for the given fluids and boundaries.
     ///
     /// Both `self.init_with_fluids` and `self.init_with_boundaries` must be called before this
@@ -3,11 +3,12 @@ use rayon::prelude::*;
 
 use na::{self, RealField, Unit};
 
+use crate::counters::Counters;
 use crate::geometry::ParticlesContacts;
 
 use crate::math::Vector;
 use crate::object::{Boundary, Fluid};
-use crate::solver::NonPressureForce;
+use crate::solver::{NonPressureForce, TemperatureCurve};
 use crate::TimestepManager;
 
 #[derive(Clone)]
@@ -18,6 +19,8 @@ use crate::TimestepManager;
 pub struct Akinci2013SurfaceTension<N: RealField> {
     fluid_tension_coefficient: N,
     boundary_adhesion_coefficient: N,
+    /// Factor multiplying the fluid tension coefficient as a function of the temperature of each particle.
+    pub temperature_curve: Option<TemperatureCurve<N>>,
     normals: Vec<Vector<N>>,
 }
 
@@ -29,6 +32,7 @@ impl<N: RealField> Akinci2013SurfaceTension<N> {
         Self {
             fluid_tension_coefficient,
             boundary_adhesion_coefficient,
+            temperature_curve: None,
             normals: Vec::new(),
         }
     }
@@ -111,6 +115,7 @@ fn adhesion_kernel<N: RealField>(r: N, h: N) -> N {
 impl<N: RealField> NonPressureForce<N> for Akinci2013SurfaceTension<N> {
     fn solve(
         &mut self,
+        _counters: &mut Counters,
         _timestep: &TimestepManager<N>,
         kernel_radius: N,
         fluid_fluid_contacts: &ParticlesContacts<N>,
@@ -132,10 +137,16 @@ impl<N: RealField> NonPressureForce<N> for Akinci2013SurfaceTension<N> {
         let density0 = fluid.density0;
         let positions = &fluid.positions;
 
+        let temperature_curve = &self.temperature_curve;
+        let temperatures = &fluid.temperatures;
+
         par_iter_mut!(fluid.accelerations)
             .enumerate()
             .for_each(|(i, acceleration_i)| {
-                if self.fluid_tension_coefficient != N::zero() {
+                let fluid_tension_coefficient = fluid_tension_coefficient
+                    * TemperatureCurve::factor(temperature_curve, temperatures[i]);
+
+                if fluid_tension_coefficient != N::zero() {
                     for c in fluid_fluid_contacts
                         .particle_contacts(i)
                         .read()
@@ -180,7 +191,8 @@ impl<N: RealField> NonPressureForce<N> for Akinci2013SurfaceTension<N> {
 
                         let mi = volumes[c.i] * density0;
                         let mj = boundaries[c.j_model].volumes[c.j] * density0;
-                        let adhesion_acc = adhesion_vec * (boundary_adhesion_coefficient * mj);
+                        let adhesion_acc = adhesion_vec
+                            * (boundary_adhesion_coefficient * boundaries[c.j_model].adhesion * mj);
                         *acceleration_i -= adhesion_acc;
 
                         boundaries[c.j_model].apply_force(c.j, adhesion_acc * mi);
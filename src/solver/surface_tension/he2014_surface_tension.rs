@@ -3,11 +3,12 @@ use rayon::prelude::*;
 
 use na::{self, RealField};
 
+use crate::counters::Counters;
 use crate::geometry::ParticlesContacts;
 
 use crate::math::Vector;
 use crate::object::{Boundary, Fluid};
-use crate::solver::NonPressureForce;
+use crate::solver::{NonPressureForce, TemperatureCurve};
 use crate::TimestepManager;
 
 // http://peridynamics.com/publications/2014-He-RSS.pdf
@@ -15,6 +16,8 @@ use crate::TimestepManager;
 pub struct He2014SurfaceTension<N: RealField> {
     fluid_tension_coefficient: N,
     boundary_tension_coefficient: N,
+    /// Factor multiplying the fluid tension coefficient as a function of the temperature of each particle.
+    pub temperature_curve: Option<TemperatureCurve<N>>,
     gradcs: Vec<N>,
     colors: Vec<N>,
 }
@@ -25,6 +28,7 @@ impl<N: RealField> He2014SurfaceTension<N> {
         Self {
             fluid_tension_coefficient,
             boundary_tension_coefficient,
+            temperature_curve: None,
             colors: Vec::new(),
             gradcs: Vec::new(),
         }
@@ -108,6 +112,7 @@ impl<N: RealField> He2014SurfaceTension<N> {
 impl<N: RealField> NonPressureForce<N> for He2014SurfaceTension<N> {
     fn solve(
         &mut self,
+        _counters: &mut Counters,
         _timestep: &TimestepManager<N>,
         _kernel_radius: N,
         fluid_fluid_contacts: &ParticlesContacts<N>,
@@ -135,9 +140,15 @@ impl<N: RealField> NonPressureForce<N> for He2014SurfaceTension<N> {
         let density0 = fluid.density0;
         let volumes = &fluid.volumes;
 
+        let temperature_curve = &self.temperature_curve;
+        let temperatures = &fluid.temperatures;
+
         par_iter_mut!(fluid.accelerations)
             .enumerate()
             .for_each(|(i, acceleration_i)| {
+                let fluid_tension_coefficient = fluid_tension_coefficient
+                    * TemperatureCurve::factor(temperature_curve, temperatures[i]);
+
                 let mi = volumes[i] * density0;
 
                 if fluid_tension_coefficient != N::zero() {
@@ -170,6 +181,7 @@ impl<N: RealField> NonPressureForce<N> for He2014SurfaceTension<N> {
                             * (mi / densities[c.i] * mj / density0
                                 * gradsum
                                 * boundary_tension_coefficient
+                                * boundaries[c.j_model].adhesion
                                 * na::convert(0.25));
                         *acceleration_i += f / mi;
 
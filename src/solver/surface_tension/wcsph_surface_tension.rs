@@ -3,10 +3,11 @@ use rayon::prelude::*;
 
 use na::{self, RealField};
 
+use crate::counters::Counters;
 use crate::geometry::ParticlesContacts;
 
 use crate::object::{Boundary, Fluid};
-use crate::solver::NonPressureForce;
+use crate::solver::{NonPressureForce, TemperatureCurve};
 use crate::TimestepManager;
 
 // Surface tension of water: 0.01
@@ -17,6 +18,8 @@ use crate::TimestepManager;
 pub struct WCSPHSurfaceTension<N: RealField> {
     fluid_tension_coefficient: N,
     boundary_tension_coefficient: N,
+    /// Factor multiplying the fluid tension coefficient as a function of the temperature of each particle.
+    pub temperature_curve: Option<TemperatureCurve<N>>,
 }
 
 impl<N: RealField> WCSPHSurfaceTension<N> {
@@ -25,6 +28,7 @@ impl<N: RealField> WCSPHSurfaceTension<N> {
         Self {
             fluid_tension_coefficient,
             boundary_tension_coefficient,
+            temperature_curve: None,
         }
     }
 }
@@ -32,10 +36,11 @@ impl<N: RealField> WCSPHSurfaceTension<N> {
 impl<N: RealField> NonPressureForce<N> for WCSPHSurfaceTension<N> {
     fn solve(
         &mut self,
+        _counters: &mut Counters,
         _timestep: &TimestepManager<N>,
         _kernel_radius: N,
         fluid_fluid_contacts: &ParticlesContacts<N>,
-        _fluid_boundaries_contacts: &ParticlesContacts<N>,
+        fluid_boundaries_contacts: &ParticlesContacts<N>,
         fluid: &mut Fluid<N>,
         boundaries: &[Boundary<N>],
         _densities: &[N],
@@ -46,9 +51,15 @@ impl<N: RealField> NonPressureForce<N> for WCSPHSurfaceTension<N> {
         let volumes = &fluid.volumes;
         let density0 = fluid.density0;
 
+        let temperature_curve = &self.temperature_curve;
+        let temperatures = &fluid.temperatures;
+
         par_iter_mut!(fluid.accelerations)
             .enumerate()
             .for_each(|(i, acceleration_i)| {
+                let fluid_tension_coefficient = fluid_tension_coefficient
+                    * TemperatureCurve::factor(temperature_curve, temperatures[i]);
+
                 if fluid_tension_coefficient != N::zero() {
                     for c in fluid_fluid_contacts
                         .particle_contacts(i)
@@ -67,7 +78,7 @@ impl<N: RealField> NonPressureForce<N> for WCSPHSurfaceTension<N> {
                 }
 
                 if boundary_tension_coefficient != N::zero() {
-                    for c in fluid_fluid_contacts
+                    for c in fluid_boundaries_contacts
                         .particle_contacts(i)
                         .read()
                         .unwrap()
@@ -77,6 +88,7 @@ impl<N: RealField> NonPressureForce<N> for WCSPHSurfaceTension<N> {
                         let mi = volumes[c.i] * density0;
                         let cohesion_force = dpos
                             * (boundary_tension_coefficient
+                                * boundaries[c.j_model].adhesion
                                 * c.weight
                                 * boundaries[c.j_model].volumes[c.j]
                                 * density0);
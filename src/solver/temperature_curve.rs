@@ -0,0 +1,57 @@
+use na::RealField;
+
+/// A piecewise-linear function of the temperature.
+///
+/// This is used to make some fluid properties (viscosity, surface tension, etc.) depend on the
+/// temperature of the fluid particles.
+#[derive(Clone, Debug)]
+pub struct TemperatureCurve<N: RealField> {
+    points: Vec<(N, N)>,
+}
+
+impl<N: RealField> TemperatureCurve<N> {
+    /// Initializes a curve interpolating the given `(temperature, value)` points.
+    ///
+    /// The value is constant bellow the smallest temperature and above the greatest temperature.
+    pub fn new(mut points: Vec<(N, N)>) -> Self {
+        assert!(
+            !points.is_empty(),
+            "A temperature curve must contain at least one point."
+        );
+        points.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+        Self { points }
+    }
+
+    /// The `(temperature, value)` points interpolated by this curve, sorted by increasing temperature.
+    pub fn points(&self) -> &[(N, N)] {
+        &self.points
+    }
+
+    /// The value of this curve at the given temperature.
+    pub fn value_at(&self, temperature: N) -> N {
+        let first = self.points[0];
+        let last = self.points[self.points.len() - 1];
+
+        if temperature <= first.0 {
+            return first.1;
+        }
+
+        if temperature >= last.0 {
+            return last.1;
+        }
+
+        // Index of the first point strictly after `temperature`.
+        let i1 = self.points.iter().position(|p| p.0 > temperature).unwrap();
+        let (t0, v0) = self.points[i1 - 1];
+        let (t1, v1) = self.points[i1];
+        v0 + (v1 - v0) * ((temperature - t0) / (t1 - t0))
+    }
+
+    /// The value of `curve` at the given temperature, or one if `curve` is `None`.
+    pub(crate) fn factor(curve: &Option<Self>, temperature: N) -> N {
+        curve
+            .as_ref()
+            .map(|c| c.value_at(temperature))
+            .unwrap_or_else(N::one)
+    }
+}
@@ -3,6 +3,7 @@ use rayon::prelude::*;
 
 use na::{self, RealField};
 
+use crate::counters::Counters;
 use crate::geometry::ParticlesContacts;
 
 use crate::math::Vector;
@@ -42,6 +43,7 @@ impl<N: RealField> ArtificialViscosity<N> {
 impl<N: RealField> NonPressureForce<N> for ArtificialViscosity<N> {
     fn solve(
         &mut self,
+        _counters: &mut Counters,
         _timestep: &TimestepManager<N>,
         kernel_radius: N,
         fluid_fluid_contacts: &ParticlesContacts<N>,
@@ -111,6 +113,7 @@ impl<N: RealField> NonPressureForce<N> for ArtificialViscosity<N> {
 
                             boundary_acc += c.gradient
                                 * (boundary_viscosity_coefficient
+                                    * boundaries[c.j_model].friction
                                     * (speed_of_sound * alpha * mu_ij - beta * mu_ij * mu_ij)
                                     * (boundaries[c.j_model].volumes[c.j] * density0
                                         / density_average));
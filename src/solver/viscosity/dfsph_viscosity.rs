@@ -3,10 +3,11 @@ use rayon::prelude::*;
 
 use na::{self, RealField};
 
+use crate::counters::Counters;
 use crate::geometry::ParticlesContacts;
-use crate::math::{Vector, SPATIAL_DIM};
+use crate::math::{Vector, DIM, SPATIAL_DIM};
 use crate::object::{Boundary, Fluid};
-use crate::solver::NonPressureForce;
+use crate::solver::{NonPressureForce, TemperatureCurve, ViscosityModel};
 use crate::TimestepManager;
 
 #[cfg(feature = "dim2")]
@@ -58,6 +59,36 @@ fn compute_strain_rate<N: RealField>(gradient: &Vector<N>, v_ji: &Vector<N>) ->
     );
 }
 
+// The shear rate `sqrt(2 D:D)` of the strain rate tensor `D` given in Voigt notation.
+fn compute_shear_rate<N: RealField>(rate: &StrainRate<N>) -> N {
+    let _2: N = na::convert(2.0f64);
+
+    #[cfg(feature = "dim2")]
+    let ddot = rate.x * rate.x + rate.y * rate.y + _2 * rate.z * rate.z;
+    #[cfg(feature = "dim3")]
+    let ddot = rate.fixed_rows::<na::U3>(0).norm_squared()
+        + _2 * rate.fixed_rows::<na::U3>(3).norm_squared();
+
+    (_2 * ddot).sqrt()
+}
+
+// The DFSPH viscosity coefficient equivalent to the dynamic viscosity `viscosity` during a timestep of length `dt`.
+//
+// The SPH viscosity laplacian damps the relative velocity of neighboring particles at the rate
+// `2 (d + 2) ν / h²` where `ν = μ / ρ` is the kinematic viscosity. The coefficient is the fraction
+// of the shearing motion removed by this damping during the timestep.
+fn viscosity_to_coefficient<N: RealField>(viscosity: N, density: N, kernel_radius: N, dt: N) -> N {
+    let d_plus_2: N = na::convert((DIM + 2) as f64);
+    let rate = d_plus_2 * na::convert(2.0) * viscosity / (density * kernel_radius * kernel_radius);
+    let coeff = N::one() - (-rate * dt).exp();
+
+    if coeff.is_finite() {
+        coeff.max(N::zero())
+    } else {
+        N::one()
+    }
+}
+
 fn compute_gradient_matrix<N: RealField>(gradient: &Vector<N>) -> BetaGradientMatrix<N> {
     let _2: N = na::convert(2.0f64);
 
@@ -83,8 +114,8 @@ fn compute_gradient_matrix<N: RealField>(gradient: &Vector<N>) -> BetaGradientMa
 
 /// Viscosity introduced with the Viscous DFSPH method.
 ///
-/// This does not include any viscosity with boundaries so it can be useful to
-/// combine this with another viscosity model and include only its boundary part.
+/// The viscosity with boundaries is not part of the original method. It is modeled here
+/// as with the XSPH viscosity, and is disabled by default.
 pub struct DFSPHViscosity<N: RealField> {
     /// Minimum number of iterations that must be executed for viscosity resolution.
     pub min_viscosity_iter: usize,
@@ -95,8 +126,18 @@ pub struct DFSPHViscosity<N: RealField> {
     /// The viscosity solver will continue iterating until the strain error drops bellow this
     /// threshold, or until the maximum number of iterations is reached.
     pub max_viscosity_error: N,
-    /// The viscosity coefficient.
+    /// The viscosity coefficient, between 0.0 and 1.0.
+    ///
+    /// This is the fraction of the shearing motion of the fluid removed at each timestep by the
+    /// `ViscosityModel::Newtonian` model. It is ignored by the other models, which are given by
+    /// a dynamic viscosity converted to such a coefficient depending on the timestep length.
     pub viscosity_coefficient: N,
+    /// The model giving the viscosity of each particle as a function of its shear rate.
+    pub viscosity_model: ViscosityModel<N>,
+    /// Factor multiplying the viscosity coefficient as a function of the temperature of each particle.
+    pub temperature_curve: Option<TemperatureCurve<N>>,
+    /// The viscosity coefficient when interacting with boundaries.
+    pub boundary_viscosity_coefficient: N,
     betas: Vec<BetaMatrix<N>>,
     strain_rates: Vec<StrainRates<N>>,
 }
@@ -114,11 +155,21 @@ impl<N: RealField> DFSPHViscosity<N> {
             max_viscosity_iter: 50,
             max_viscosity_error: na::convert(0.01),
             viscosity_coefficient,
+            viscosity_model: ViscosityModel::Newtonian,
+            temperature_curve: None,
+            boundary_viscosity_coefficient: N::zero(),
             betas: Vec::new(),
             strain_rates: Vec::new(),
         }
     }
 
+    /// Initialize a new DFSPH viscosity solver with a shear-rate-dependent viscosity model.
+    pub fn with_model(viscosity_model: ViscosityModel<N>) -> Self {
+        let mut result = Self::new(N::zero());
+        result.viscosity_model = viscosity_model;
+        result
+    }
+
     fn init(&mut self, fluid: &Fluid<N>) {
         if self.betas.len() != fluid.num_particles() {
             self.betas
@@ -200,6 +251,7 @@ impl<N: RealField> DFSPHViscosity<N> {
     fn compute_strain_rates(
         &mut self,
         timestep: &TimestepManager<N>,
+        kernel_radius: N,
         fluid_fluid_contacts: &ParticlesContacts<N>,
         fluid: &Fluid<N>,
         densities: &[N],
@@ -207,6 +259,8 @@ impl<N: RealField> DFSPHViscosity<N> {
     ) -> N {
         let mut max_error = N::zero();
         let viscosity_coefficient = self.viscosity_coefficient;
+        let viscosity_model = &self.viscosity_model;
+        let temperature_curve = &self.temperature_curve;
         let _2: N = na::convert(2.0f64);
 
         let it = par_iter_mut!(self.strain_rates)
@@ -234,7 +288,23 @@ impl<N: RealField> DFSPHViscosity<N> {
                     strain_rates_i.error = fluid_rate - strain_rates_i.target;
                     strain_rates_i.error.lp_norm(1) / na::convert(6.0f64)
                 } else {
-                    strain_rates_i.target = fluid_rate * (N::one() - viscosity_coefficient);
+                    let temperature_factor =
+                        TemperatureCurve::factor(temperature_curve, fluid.temperatures[i]);
+                    let shear_rate = compute_shear_rate(&fluid_rate);
+                    let coeff = match viscosity_model.viscosity(shear_rate) {
+                        Some(viscosity) => viscosity_to_coefficient(
+                            viscosity * temperature_factor,
+                            densities[i],
+                            kernel_radius,
+                            timestep.dt(),
+                        ),
+                        None => na::clamp(
+                            viscosity_coefficient * temperature_factor,
+                            N::zero(),
+                            N::one(),
+                        ),
+                    };
+                    strain_rates_i.target = fluid_rate * (N::one() - coeff);
                     N::zero()
                 }
             });
@@ -286,28 +356,84 @@ impl<N: RealField> DFSPHViscosity<N> {
                 }
             })
     }
+
+    fn compute_boundary_accelerations(
+        &self,
+        timestep: &TimestepManager<N>,
+        fluid_boundaries_contacts: &ParticlesContacts<N>,
+        fluid: &mut Fluid<N>,
+        boundaries: &[Boundary<N>],
+        densities: &[N],
+    ) {
+        let boundary_viscosity_coefficient = self.boundary_viscosity_coefficient;
+        let velocities = &fluid.velocities;
+        let volumes = &fluid.volumes;
+        let density0 = fluid.density0;
+
+        par_iter_mut!(fluid.accelerations)
+            .enumerate()
+            .for_each(|(i, acceleration)| {
+                let mut added_vel = Vector::zeros();
+                let vi = velocities[i];
+                let mi = volumes[i] * density0;
+
+                for c in fluid_boundaries_contacts
+                    .particle_contacts(i)
+                    .read()
+                    .unwrap()
+                    .iter()
+                {
+                    let boundary = &boundaries[c.j_model];
+                    let delta = (boundary.velocities[c.j] - vi)
+                        * (boundary_viscosity_coefficient
+                            * boundary.friction
+                            * c.weight
+                            * boundary.volumes[c.j]
+                            * density0
+                            / densities[c.i]);
+                    added_vel += delta;
+                    boundary.apply_force(c.j, delta * (-mi * timestep.inv_dt()));
+                }
+
+                *acceleration += added_vel * timestep.inv_dt();
+            })
+    }
 }
 
 impl<N: RealField> NonPressureForce<N> for DFSPHViscosity<N> {
     fn solve(
         &mut self,
+        counters: &mut Counters,
         timestep: &TimestepManager<N>,
-        _kernel_radius: N,
+        kernel_radius: N,
         fluid_fluid_contacts: &ParticlesContacts<N>,
-        _fluid_boundaries_contacts: &ParticlesContacts<N>,
+        fluid_boundaries_contacts: &ParticlesContacts<N>,
         fluid: &mut Fluid<N>,
-        _boundaries: &[Boundary<N>],
+        boundaries: &[Boundary<N>],
         densities: &[N],
     ) {
         self.init(fluid);
 
         let _ = self.compute_betas(fluid_fluid_contacts, fluid, densities);
 
-        let _ = self.compute_strain_rates(timestep, fluid_fluid_contacts, fluid, densities, false);
+        let _ = self.compute_strain_rates(
+            timestep,
+            kernel_radius,
+            fluid_fluid_contacts,
+            fluid,
+            densities,
+            false,
+        );
 
         for i in 0..self.max_viscosity_iter {
-            let avg_err =
-                self.compute_strain_rates(timestep, fluid_fluid_contacts, fluid, densities, true);
+            let avg_err = self.compute_strain_rates(
+                timestep,
+                kernel_radius,
+                fluid_fluid_contacts,
+                fluid,
+                densities,
+                true,
+            );
 
             if avg_err <= self.max_viscosity_error && i >= self.min_viscosity_iter {
                 //                println!(
@@ -320,8 +446,41 @@ impl<N: RealField> NonPressureForce<N> for DFSPHViscosity<N> {
             }
 
             self.compute_accelerations(timestep, fluid_fluid_contacts, fluid, densities);
+            counters.solver.viscosity_iterations += 1;
+        }
+
+        if self.boundary_viscosity_coefficient != N::zero() {
+            self.compute_boundary_accelerations(
+                timestep,
+                fluid_boundaries_contacts,
+                fluid,
+                boundaries,
+                densities,
+            );
         }
     }
 
     fn apply_permutation(&mut self, _: &[usize]) {}
 }
+
+#[cfg(test)]
+mod test {
+    use super::viscosity_to_coefficient;
+
+    #[test]
+    fn viscosity_coefficient_depends_on_timestep() {
+        let coeff = |viscosity: f64, dt: f64| viscosity_to_coefficient(viscosity, 1000.0, 0.1, dt);
+
+        assert_eq!(coeff(0.0, 0.01), 0.0);
+        assert_eq!(coeff(std::f64::INFINITY, 0.01), 1.0);
+
+        // Doubling the timestep applies the damping of two timesteps at once.
+        let (c1, c2) = (coeff(1.0, 0.01), coeff(1.0, 0.02));
+        assert!(c1 > 0.0 && c1 < 1.0);
+        assert!(((1.0 - c1) * (1.0 - c1) - (1.0 - c2)).abs() < 1.0e-12);
+
+        // Very viscous fluids approach the rigid limit without exceeding it.
+        let c3 = coeff(1.0e6, 0.01);
+        assert!(c3 > 0.999 && c3 <= 1.0);
+    }
+}
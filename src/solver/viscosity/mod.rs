@@ -1,7 +1,11 @@
 pub use self::artificial_viscosity::ArtificialViscosity;
 pub use self::dfsph_viscosity::DFSPHViscosity;
+pub use self::viscosity_model::ViscosityModel;
+pub use self::weiler2018_viscosity::Weiler2018Viscosity;
 pub use self::xsph_viscosity::XSPHViscosity;
 
 mod artificial_viscosity;
 mod dfsph_viscosity;
+mod viscosity_model;
+mod weiler2018_viscosity;
 mod xsph_viscosity;
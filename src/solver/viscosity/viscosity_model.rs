@@ -0,0 +1,177 @@
+use na::{self, RealField};
+
+/// A model giving the viscosity of a fluid as a function of its shear rate.
+///
+/// Except for the `Newtonian` model, these models give the dynamic viscosity `μ` of the fluid
+/// (in Pa·s) as a function of its shear rate `γ` (in s⁻¹). The `DFSPHViscosity` converts this
+/// viscosity to its dimensionless per-timestep coefficient depending on the timestep length,
+/// the density of the fluid, and the kernel radius.
+#[derive(Copy, Clone, Debug)]
+pub enum ViscosityModel<N: RealField> {
+    /// Newtonian fluid with the constant viscosity coefficient `DFSPHViscosity::viscosity_coefficient`.
+    Newtonian,
+    /// Ostwald–de Waele power-law fluid: `μ = k γ^(n - 1)`.
+    ///
+    /// Shear-thinning for `n < 1` (e.g. paint), shear-thickening for `n > 1`.
+    PowerLaw {
+        /// The flow consistency index `k`.
+        consistency: N,
+        /// The flow behavior index `n`.
+        flow_index: N,
+    },
+    /// Cross fluid: `μ = μ_inf + (μ_0 - μ_inf) / (1 + (λ γ)^m)`.
+    Cross {
+        /// The viscosity `μ_0` at zero shear rate.
+        zero_shear_viscosity: N,
+        /// The viscosity `μ_inf` at infinite shear rate.
+        infinite_shear_viscosity: N,
+        /// The time constant `λ`.
+        time_constant: N,
+        /// The rate constant `m`.
+        rate_index: N,
+    },
+    /// Carreau fluid: `μ = μ_inf + (μ_0 - μ_inf) (1 + (λ γ)²)^((n - 1) / 2)`.
+    Carreau {
+        /// The viscosity `μ_0` at zero shear rate.
+        zero_shear_viscosity: N,
+        /// The viscosity `μ_inf` at infinite shear rate.
+        infinite_shear_viscosity: N,
+        /// The relaxation time `λ`.
+        relaxation_time: N,
+        /// The power index `n`.
+        power_index: N,
+    },
+    /// Herschel–Bulkley fluid: `μ = τ_0 / γ + k γ^(n - 1)`.
+    ///
+    /// The viscosity of this fluid tends to infinity at low shear rates, so it barely flows as long
+    /// as its shear stress is smaller than the yield stress `τ_0` (e.g. ketchup, toothpaste).
+    /// A Bingham plastic is a Herschel–Bulkley fluid with `n = 1`.
+    HerschelBulkley {
+        /// The yield stress `τ_0`.
+        yield_stress: N,
+        /// The flow consistency index `k`.
+        consistency: N,
+        /// The flow behavior index `n`.
+        flow_index: N,
+    },
+}
+
+impl<N: RealField> ViscosityModel<N> {
+    /// A Bingham plastic with the given yield stress and plastic viscosity.
+    pub fn bingham(yield_stress: N, plastic_viscosity: N) -> Self {
+        ViscosityModel::HerschelBulkley {
+            yield_stress,
+            consistency: plastic_viscosity,
+            flow_index: N::one(),
+        }
+    }
+
+    /// The dynamic viscosity `μ` for the given shear rate.
+    ///
+    /// Returns `None` for the `Newtonian` model, which is not given by a physical viscosity.
+    pub fn viscosity(&self, shear_rate: N) -> Option<N> {
+        // Avoid infinite viscosities at rest.
+        let shear_rate = shear_rate.max(N::default_epsilon());
+
+        match *self {
+            ViscosityModel::Newtonian => None,
+            ViscosityModel::PowerLaw {
+                consistency,
+                flow_index,
+            } => Some(consistency * shear_rate.powf(flow_index - N::one())),
+            ViscosityModel::Cross {
+                zero_shear_viscosity,
+                infinite_shear_viscosity,
+                time_constant,
+                rate_index,
+            } => Some(
+                infinite_shear_viscosity
+                    + (zero_shear_viscosity - infinite_shear_viscosity)
+                        / (N::one() + (time_constant * shear_rate).powf(rate_index)),
+            ),
+            ViscosityModel::Carreau {
+                zero_shear_viscosity,
+                infinite_shear_viscosity,
+                relaxation_time,
+                power_index,
+            } => {
+                let lambda_rate = relaxation_time * shear_rate;
+                Some(
+                    infinite_shear_viscosity
+                        + (zero_shear_viscosity - infinite_shear_viscosity)
+                            * (N::one() + lambda_rate * lambda_rate)
+                                .powf((power_index - N::one()) * na::convert(0.5)),
+                )
+            }
+            ViscosityModel::HerschelBulkley {
+                yield_stress,
+                consistency,
+                flow_index,
+            } => Some(
+                yield_stress / shear_rate + consistency * shear_rate.powf(flow_index - N::one()),
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ViscosityModel;
+
+    #[test]
+    fn newtonian_has_no_physical_viscosity() {
+        assert_eq!(ViscosityModel::<f64>::Newtonian.viscosity(10.0), None);
+    }
+
+    #[test]
+    fn power_law_viscosity() {
+        let thinning = ViscosityModel::<f64>::PowerLaw {
+            consistency: 2.0,
+            flow_index: 0.5,
+        };
+        let mu = thinning.viscosity(4.0).unwrap();
+        assert!((mu - 1.0).abs() < 1.0e-12);
+        assert!(thinning.viscosity(100.0).unwrap() < mu);
+
+        let thickening = ViscosityModel::<f64>::PowerLaw {
+            consistency: 2.0,
+            flow_index: 2.0,
+        };
+        assert!((thickening.viscosity(4.0).unwrap() - 8.0).abs() < 1.0e-12);
+    }
+
+    #[test]
+    fn cross_and_carreau_limits() {
+        let cross = ViscosityModel::<f64>::Cross {
+            zero_shear_viscosity: 10.0,
+            infinite_shear_viscosity: 0.1,
+            time_constant: 1.0,
+            rate_index: 1.0,
+        };
+        let carreau = ViscosityModel::<f64>::Carreau {
+            zero_shear_viscosity: 10.0,
+            infinite_shear_viscosity: 0.1,
+            relaxation_time: 1.0,
+            power_index: 0.5,
+        };
+
+        for model in &[cross, carreau] {
+            assert!((model.viscosity(0.0).unwrap() - 10.0).abs() < 1.0e-6);
+            assert!((model.viscosity(1.0e12).unwrap() - 0.1).abs() < 1.0e-3);
+        }
+
+        // At `λ γ = 1`, the Cross viscosity is halfway between both limits.
+        assert!((cross.viscosity(1.0).unwrap() - 5.05).abs() < 1.0e-12);
+    }
+
+    #[test]
+    fn bingham_yields_under_stress() {
+        let bingham = ViscosityModel::<f64>::bingham(5.0, 0.5);
+
+        // The shear stress `μ γ` is the yield stress plus the plastic part.
+        let stress = |rate: f64| bingham.viscosity(rate).unwrap() * rate;
+        assert!((stress(2.0) - 6.0).abs() < 1.0e-12);
+        assert!((stress(10.0) - 10.0).abs() < 1.0e-12);
+        assert!(bingham.viscosity(0.0).unwrap() > 1.0e10);
+    }
+}
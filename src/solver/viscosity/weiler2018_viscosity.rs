@@ -0,0 +1,420 @@
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+use na::{self, RealField};
+
+use crate::counters::Counters;
+use crate::geometry::ParticlesContacts;
+use crate::math::{Matrix, Vector, DIM};
+use crate::object::{Boundary, Fluid};
+use crate::solver::{NonPressureForce, TemperatureCurve};
+use crate::TimestepManager;
+
+// The coefficient `c_ij` such that the viscous force applied by `j` on `i` is
+// `c_ij * gradient * dot(x_ij, v_ij)`.
+fn pair_coefficient<N: RealField>(
+    viscosity_coefficient: N,
+    mi: N,
+    vj: N,
+    x_ij: &Vector<N>,
+    kernel_radius: N,
+) -> N {
+    let eta2 = kernel_radius * kernel_radius * na::convert(0.01);
+    let d_plus_2: N = na::convert((DIM + 2) as f64);
+    d_plus_2 * na::convert(2.0) * viscosity_coefficient * mi * vj / (x_ij.norm_squared() + eta2)
+}
+
+/// Implicit viscosity introduced by Weiler et al. 2018.
+///
+/// The viscous forces are computed by solving the linear system resulting from an implicit
+/// integration of the SPH viscosity laplacian, using a matrix-free conjugate gradient. This
+/// remains stable for very high viscosities (honey, lava, etc.)
+pub struct Weiler2018Viscosity<N: RealField> {
+    /// Maximum number of iterations of the conjugate gradient.
+    pub max_viscosity_iter: usize,
+    /// Maximum acceptable relative residual of the conjugate gradient.
+    ///
+    /// The viscosity solver will continue iterating until the norm of the residual divided by the norm
+    /// of the right-hand-side drops bellow this threshold, or until the maximum number of iterations is reached.
+    pub max_viscosity_error: N,
+    /// The kinematic viscosity of the fluid.
+    pub fluid_viscosity_coefficient: N,
+    /// The kinematic viscosity of the fluid when interacting with boundaries.
+    pub boundary_viscosity_coefficient: N,
+    /// Factor multiplying the viscosity coefficients as a function of the temperature of each particle.
+    pub temperature_curve: Option<TemperatureCurve<N>>,
+    temperature_factors: Vec<N>,
+    predicted_velocities: Vec<Vector<N>>,
+    boundary_blocks: Vec<Matrix<N>>,
+    inv_diag: Vec<Vector<N>>,
+    rhs: Vec<Vector<N>>,
+    solution: Vec<Vector<N>>,
+    residual: Vec<Vector<N>>,
+    preconditioned_residual: Vec<Vector<N>>,
+    direction: Vec<Vector<N>>,
+    matrix_times_direction: Vec<Vector<N>>,
+}
+
+impl<N: RealField> Weiler2018Viscosity<N> {
+    /// Initializes the implicit viscosity with the given viscosity coefficients.
+    pub fn new(fluid_viscosity_coefficient: N, boundary_viscosity_coefficient: N) -> Self {
+        Self {
+            max_viscosity_iter: 100,
+            max_viscosity_error: na::convert(0.01),
+            fluid_viscosity_coefficient,
+            boundary_viscosity_coefficient,
+            temperature_curve: None,
+            temperature_factors: Vec::new(),
+            predicted_velocities: Vec::new(),
+            boundary_blocks: Vec::new(),
+            inv_diag: Vec::new(),
+            rhs: Vec::new(),
+            solution: Vec::new(),
+            residual: Vec::new(),
+            preconditioned_residual: Vec::new(),
+            direction: Vec::new(),
+            matrix_times_direction: Vec::new(),
+        }
+    }
+
+    fn init(&mut self, fluid: &Fluid<N>) {
+        let nparts = fluid.num_particles();
+
+        if self.rhs.len() != nparts {
+            self.temperature_factors.resize(nparts, N::one());
+            self.predicted_velocities.resize(nparts, Vector::zeros());
+            self.boundary_blocks.resize(nparts, Matrix::zeros());
+            self.inv_diag.resize(nparts, Vector::zeros());
+            self.rhs.resize(nparts, Vector::zeros());
+            self.solution.resize(nparts, Vector::zeros());
+            self.residual.resize(nparts, Vector::zeros());
+            self.preconditioned_residual.resize(nparts, Vector::zeros());
+            self.direction.resize(nparts, Vector::zeros());
+            self.matrix_times_direction.resize(nparts, Vector::zeros());
+        }
+    }
+
+    // Computes the right-hand-side, the boundary contributions to the system matrix, and the
+    // diagonal preconditioner.
+    fn setup_system(
+        &mut self,
+        timestep: &TimestepManager<N>,
+        kernel_radius: N,
+        fluid_fluid_contacts: &ParticlesContacts<N>,
+        fluid_boundaries_contacts: &ParticlesContacts<N>,
+        fluid: &Fluid<N>,
+        boundaries: &[Boundary<N>],
+    ) {
+        let dt = timestep.dt();
+        let fluid_viscosity_coefficient = self.fluid_viscosity_coefficient;
+        let boundary_viscosity_coefficient = self.boundary_viscosity_coefficient;
+
+        par_iter_mut!(self.predicted_velocities)
+            .enumerate()
+            .for_each(|(i, v)| *v = fluid.velocities[i] + fluid.accelerations[i] * dt);
+
+        let temperature_curve = &self.temperature_curve;
+        par_iter_mut!(self.temperature_factors)
+            .enumerate()
+            .for_each(|(i, f)| {
+                *f = TemperatureCurve::factor(temperature_curve, fluid.temperatures[i])
+            });
+
+        let predicted_velocities = &self.predicted_velocities;
+        let temperature_factors = &self.temperature_factors;
+
+        par_iter_mut!(self.rhs)
+            .zip(par_iter_mut!(self.boundary_blocks))
+            .zip(par_iter_mut!(self.inv_diag))
+            .enumerate()
+            .for_each(|(i, ((rhs_i, block_i), inv_diag_i))| {
+                let mi = fluid.particle_mass(i);
+                let mut diag = Vector::repeat(mi);
+                *rhs_i = predicted_velocities[i] * mi;
+                *block_i = Matrix::zeros();
+
+                if fluid_viscosity_coefficient != N::zero() {
+                    for c in fluid_fluid_contacts
+                        .particle_contacts(i)
+                        .read()
+                        .unwrap()
+                        .iter()
+                    {
+                        if c.i_model == c.j_model {
+                            let x_ij = fluid.positions[c.i] - fluid.positions[c.j];
+                            let coeff = pair_coefficient(
+                                fluid_viscosity_coefficient
+                                    * (temperature_factors[c.i] + temperature_factors[c.j])
+                                    * na::convert(0.5),
+                                mi,
+                                fluid.volumes[c.j],
+                                &x_ij,
+                                kernel_radius,
+                            );
+                            diag -= c.gradient.component_mul(&x_ij) * (coeff * dt);
+                        }
+                    }
+                }
+
+                if boundary_viscosity_coefficient != N::zero() {
+                    for c in fluid_boundaries_contacts
+                        .particle_contacts(i)
+                        .read()
+                        .unwrap()
+                        .iter()
+                    {
+                        let boundary = &boundaries[c.j_model];
+                        let x_ij = fluid.positions[c.i] - boundary.positions[c.j];
+                        let coeff = pair_coefficient(
+                            boundary_viscosity_coefficient
+                                * boundary.friction
+                                * temperature_factors[c.i],
+                            mi,
+                            boundary.volumes[c.j],
+                            &x_ij,
+                            kernel_radius,
+                        );
+                        let block = c.gradient * x_ij.transpose() * -coeff;
+                        *block_i += block;
+                        *rhs_i += block * boundary.velocities[c.j] * dt;
+                    }
+
+                    diag += block_i.diagonal() * dt;
+                }
+
+                *inv_diag_i = diag.map(|d| if d.is_zero() { N::zero() } else { N::one() / d });
+            })
+    }
+
+    // Computes `out = A * v` where `A` is the (symmetric positive-definite) system matrix.
+    fn apply_system_matrix(
+        timestep: &TimestepManager<N>,
+        kernel_radius: N,
+        fluid_viscosity_coefficient: N,
+        fluid_fluid_contacts: &ParticlesContacts<N>,
+        fluid: &Fluid<N>,
+        temperature_factors: &[N],
+        boundary_blocks: &[Matrix<N>],
+        v: &[Vector<N>],
+        out: &mut [Vector<N>],
+    ) {
+        let dt = timestep.dt();
+
+        par_iter_mut!(out).enumerate().for_each(|(i, out_i)| {
+            let mi = fluid.particle_mass(i);
+            let mut laplacian = Vector::zeros();
+
+            if fluid_viscosity_coefficient != N::zero() {
+                for c in fluid_fluid_contacts
+                    .particle_contacts(i)
+                    .read()
+                    .unwrap()
+                    .iter()
+                {
+                    if c.i_model == c.j_model {
+                        let x_ij = fluid.positions[c.i] - fluid.positions[c.j];
+                        let v_ij = v[c.i] - v[c.j];
+                        let coeff = pair_coefficient(
+                            fluid_viscosity_coefficient
+                                * (temperature_factors[c.i] + temperature_factors[c.j])
+                                * na::convert(0.5),
+                            mi,
+                            fluid.volumes[c.j],
+                            &x_ij,
+                            kernel_radius,
+                        );
+                        laplacian += c.gradient * (coeff * x_ij.dot(&v_ij));
+                    }
+                }
+            }
+
+            *out_i = v[i] * mi - (laplacian - boundary_blocks[i] * v[i]) * dt;
+        })
+    }
+
+    fn solve_system(
+        &mut self,
+        timestep: &TimestepManager<N>,
+        kernel_radius: N,
+        fluid_fluid_contacts: &ParticlesContacts<N>,
+        fluid: &Fluid<N>,
+    ) -> usize {
+        let fluid_viscosity_coefficient = self.fluid_viscosity_coefficient;
+
+        // Warm-start with the predicted velocities.
+        self.solution.copy_from_slice(&self.predicted_velocities);
+        Self::apply_system_matrix(
+            timestep,
+            kernel_radius,
+            fluid_viscosity_coefficient,
+            fluid_fluid_contacts,
+            fluid,
+            &self.temperature_factors,
+            &self.boundary_blocks,
+            &self.solution,
+            &mut self.matrix_times_direction,
+        );
+
+        let inv_diag = &self.inv_diag;
+        let rhs = &self.rhs;
+        let matrix_times_solution = &self.matrix_times_direction;
+
+        par_iter_mut!(self.residual)
+            .zip(par_iter_mut!(self.preconditioned_residual))
+            .zip(par_iter_mut!(self.direction))
+            .enumerate()
+            .for_each(|(i, ((r, z), p))| {
+                *r = rhs[i] - matrix_times_solution[i];
+                *z = r.component_mul(&inv_diag[i]);
+                *p = *z;
+            });
+
+        let rhs_norm = par_reduce_sum!(N::zero(), par_iter!(self.rhs).map(|b| b.norm_squared()))
+            .sqrt()
+            .max(N::default_epsilon());
+        let mut rz = par_reduce_sum!(
+            N::zero(),
+            par_iter!(self.residual)
+                .zip(par_iter!(self.preconditioned_residual))
+                .map(|(r, z)| r.dot(z))
+        );
+
+        for niter in 0..self.max_viscosity_iter {
+            let residual_norm = par_reduce_sum!(
+                N::zero(),
+                par_iter!(self.residual).map(|r| r.norm_squared())
+            )
+            .sqrt();
+
+            if residual_norm / rhs_norm <= self.max_viscosity_error || rz.is_zero() {
+                return niter;
+            }
+
+            Self::apply_system_matrix(
+                timestep,
+                kernel_radius,
+                fluid_viscosity_coefficient,
+                fluid_fluid_contacts,
+                fluid,
+                &self.temperature_factors,
+                &self.boundary_blocks,
+                &self.direction,
+                &mut self.matrix_times_direction,
+            );
+
+            let p_ap = par_reduce_sum!(
+                N::zero(),
+                par_iter!(self.direction)
+                    .zip(par_iter!(self.matrix_times_direction))
+                    .map(|(p, ap)| p.dot(ap))
+            );
+
+            if p_ap <= N::zero() {
+                return niter;
+            }
+
+            let alpha = rz / p_ap;
+            let direction = &self.direction;
+            let matrix_times_direction = &self.matrix_times_direction;
+
+            par_iter_mut!(self.solution)
+                .zip(par_iter_mut!(self.residual))
+                .zip(par_iter_mut!(self.preconditioned_residual))
+                .enumerate()
+                .for_each(|(i, ((x, r), z))| {
+                    *x += direction[i] * alpha;
+                    *r -= matrix_times_direction[i] * alpha;
+                    *z = r.component_mul(&inv_diag[i]);
+                });
+
+            let new_rz = par_reduce_sum!(
+                N::zero(),
+                par_iter!(self.residual)
+                    .zip(par_iter!(self.preconditioned_residual))
+                    .map(|(r, z)| r.dot(z))
+            );
+            let beta = new_rz / rz;
+            rz = new_rz;
+
+            let preconditioned_residual = &self.preconditioned_residual;
+            par_iter_mut!(self.direction)
+                .enumerate()
+                .for_each(|(i, p)| *p = preconditioned_residual[i] + *p * beta);
+        }
+
+        self.max_viscosity_iter
+    }
+}
+
+impl<N: RealField> NonPressureForce<N> for Weiler2018Viscosity<N> {
+    fn solve(
+        &mut self,
+        counters: &mut Counters,
+        timestep: &TimestepManager<N>,
+        kernel_radius: N,
+        fluid_fluid_contacts: &ParticlesContacts<N>,
+        fluid_boundaries_contacts: &ParticlesContacts<N>,
+        fluid: &mut Fluid<N>,
+        boundaries: &[Boundary<N>],
+        _densities: &[N],
+    ) {
+        if timestep.dt().is_zero()
+            || (self.fluid_viscosity_coefficient.is_zero()
+                && self.boundary_viscosity_coefficient.is_zero())
+        {
+            return;
+        }
+
+        self.init(fluid);
+        self.setup_system(
+            timestep,
+            kernel_radius,
+            fluid_fluid_contacts,
+            fluid_boundaries_contacts,
+            fluid,
+            boundaries,
+        );
+        counters.solver.viscosity_iterations +=
+            self.solve_system(timestep, kernel_radius, fluid_fluid_contacts, fluid);
+
+        let inv_dt = timestep.inv_dt();
+        let solution = &self.solution;
+        let predicted_velocities = &self.predicted_velocities;
+        let boundary_viscosity_coefficient = self.boundary_viscosity_coefficient;
+        let temperature_factors = &self.temperature_factors;
+        let positions = &fluid.positions;
+        let volumes = &fluid.volumes;
+        let density0 = fluid.density0;
+
+        par_iter_mut!(fluid.accelerations)
+            .enumerate()
+            .for_each(|(i, acceleration)| {
+                *acceleration += (solution[i] - predicted_velocities[i]) * inv_dt;
+
+                if boundary_viscosity_coefficient != N::zero() {
+                    let mi = volumes[i] * density0;
+
+                    for c in fluid_boundaries_contacts
+                        .particle_contacts(i)
+                        .read()
+                        .unwrap()
+                        .iter()
+                    {
+                        let boundary = &boundaries[c.j_model];
+                        let x_ij = positions[c.i] - boundary.positions[c.j];
+                        let v_ij = solution[c.i] - boundary.velocities[c.j];
+                        let coeff = pair_coefficient(
+                            boundary_viscosity_coefficient
+                                * boundary.friction
+                                * temperature_factors[c.i],
+                            mi,
+                            boundary.volumes[c.j],
+                            &x_ij,
+                            kernel_radius,
+                        );
+                        boundary.apply_force(c.j, c.gradient * (-coeff * x_ij.dot(&v_ij)));
+                    }
+                }
+            })
+    }
+}
@@ -3,6 +3,7 @@ use rayon::prelude::*;
 
 use na::{self, RealField};
 
+use crate::counters::Counters;
 use crate::geometry::ParticlesContacts;
 
 use crate::math::Vector;
@@ -32,6 +33,7 @@ impl<N: RealField> XSPHViscosity<N> {
 impl<N: RealField> NonPressureForce<N> for XSPHViscosity<N> {
     fn solve(
         &mut self,
+        _counters: &mut Counters,
         timestep: &TimestepManager<N>,
         _kernel_radius: N,
         fluid_fluid_contacts: &ParticlesContacts<N>,
@@ -78,16 +80,18 @@ impl<N: RealField> NonPressureForce<N> for XSPHViscosity<N> {
                         .unwrap()
                         .iter()
                     {
-                        let delta = (boundaries[c.j_model].velocities[c.j] - vi)
+                        let boundary = &boundaries[c.j_model];
+                        let delta = (boundary.velocities[c.j] - vi)
                             * (boundary_viscosity_coefficient
+                                * boundary.friction
                                 * c.weight
-                                * boundaries[c.j_model].volumes[c.j]
+                                * boundary.volumes[c.j]
                                 * density0
                                 / densities[c.i]);
                         added_boundary_vel += delta;
 
                         let mi = volumes[c.i] * density0;
-                        boundaries[c.j_model].apply_force(c.j, delta * (-mi * timestep.inv_dt()));
+                        boundary.apply_force(c.j, delta * (-mi * timestep.inv_dt()));
                     }
                 }
 
@@ -0,0 +1,171 @@
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+use na::{self, RealField};
+
+use crate::counters::Counters;
+use crate::geometry::ParticlesContacts;
+use crate::math::{self, AngularVector};
+use crate::object::{Boundary, Fluid};
+use crate::solver::vorticity::vorticity_confinement::compute_vorticities;
+use crate::solver::NonPressureForce;
+use crate::TimestepManager;
+
+// https://animation.rwth-aachen.de/media/papers/2017-TVCG-MicropolarSPH.pdf
+/// Micropolar turbulence model introduced by Bender et al. 2017.
+///
+/// Each particle carries an angular velocity coupled with the vorticity of the fluid. This
+/// angular velocity feeds energy back into the linear velocity field, adding turbulent details
+/// that would otherwise be dissipated by the SPH discretization.
+#[derive(Clone)]
+pub struct Bender2017Micropolar<N: RealField> {
+    /// The vortex viscosity `ν_t` controlling the coupling between the linear and angular velocities.
+    pub vortex_viscosity: N,
+    /// The angular viscosity `ζ` smoothing the angular velocities between neighboring particles.
+    ///
+    /// This is a coefficient between 0.0 and 1.0, similar to the XSPH viscosity coefficient.
+    pub angular_viscosity: N,
+    /// The micro-inertia `θ` of the particles.
+    pub micro_inertia: N,
+    angular_velocities: Vec<AngularVector<N>>,
+    vorticities: Vec<AngularVector<N>>,
+}
+
+impl<N: RealField> Bender2017Micropolar<N> {
+    /// Initializes the micropolar model with the given vortex viscosity, angular viscosity, and micro-inertia.
+    pub fn new(vortex_viscosity: N, angular_viscosity: N, micro_inertia: N) -> Self {
+        Self {
+            vortex_viscosity,
+            angular_viscosity,
+            micro_inertia,
+            angular_velocities: Vec::new(),
+            vorticities: Vec::new(),
+        }
+    }
+
+    /// The angular velocity of each particle of the fluid this model is attached to.
+    pub fn angular_velocities(&self) -> &[AngularVector<N>] {
+        &self.angular_velocities
+    }
+
+    fn init(&mut self, fluid: &Fluid<N>) {
+        if self.angular_velocities.len() != fluid.num_particles() {
+            self.angular_velocities
+                .resize(fluid.num_particles(), AngularVector::zeros());
+            self.vorticities
+                .resize(fluid.num_particles(), AngularVector::zeros());
+        }
+    }
+
+    fn integrate_angular_velocities(
+        &mut self,
+        dt: N,
+        fluid_fluid_contacts: &ParticlesContacts<N>,
+        fluid: &Fluid<N>,
+        densities: &[N],
+    ) {
+        let _2: N = na::convert(2.0);
+        let coeff = dt * self.vortex_viscosity / self.micro_inertia;
+        let vorticities = &self.vorticities;
+
+        // Semi-implicit integration of `θ dω/dt = ν_t (curl(v) - 2ω)`.
+        par_iter_mut!(self.angular_velocities)
+            .enumerate()
+            .for_each(|(i, omega)| {
+                *omega = (*omega + vorticities[i] * coeff) / (N::one() + _2 * coeff);
+            });
+
+        if !self.angular_viscosity.is_zero() {
+            let angular_viscosity = self.angular_viscosity;
+            let angular_velocities = &self.angular_velocities;
+
+            // Use `self.vorticities` as a buffer for the smoothed angular velocities.
+            par_iter_mut!(self.vorticities)
+                .enumerate()
+                .for_each(|(i, smoothed_omega)| {
+                    let mut added_omega = AngularVector::zeros();
+
+                    for c in fluid_fluid_contacts
+                        .particle_contacts(i)
+                        .read()
+                        .unwrap()
+                        .iter()
+                    {
+                        if c.i_model == c.j_model {
+                            added_omega += (angular_velocities[c.j] - angular_velocities[c.i])
+                                * (c.weight * fluid.particle_mass(c.j) / densities[c.j]);
+                        }
+                    }
+
+                    *smoothed_omega = angular_velocities[i] + added_omega * angular_viscosity;
+                });
+
+            std::mem::swap(&mut self.angular_velocities, &mut self.vorticities);
+        }
+    }
+}
+
+impl<N: RealField> NonPressureForce<N> for Bender2017Micropolar<N> {
+    fn solve(
+        &mut self,
+        _counters: &mut Counters,
+        timestep: &TimestepManager<N>,
+        _kernel_radius: N,
+        fluid_fluid_contacts: &ParticlesContacts<N>,
+        _fluid_boundaries_contacts: &ParticlesContacts<N>,
+        fluid: &mut Fluid<N>,
+        _boundaries: &[Boundary<N>],
+        densities: &[N],
+    ) {
+        self.init(fluid);
+
+        if timestep.dt().is_zero() || self.micro_inertia.is_zero() {
+            return;
+        }
+
+        compute_vorticities(
+            fluid_fluid_contacts,
+            fluid,
+            densities,
+            &mut self.vorticities,
+        );
+        self.integrate_angular_velocities(timestep.dt(), fluid_fluid_contacts, fluid, densities);
+
+        // Apply `ν_t curl(ω)` to the linear velocities.
+        let vortex_viscosity = self.vortex_viscosity;
+        let angular_velocities = &self.angular_velocities;
+        let volumes = &fluid.volumes;
+        let density0 = fluid.density0;
+
+        par_iter_mut!(fluid.accelerations)
+            .enumerate()
+            .for_each(|(i, acceleration)| {
+                for c in fluid_fluid_contacts
+                    .particle_contacts(i)
+                    .read()
+                    .unwrap()
+                    .iter()
+                {
+                    if c.i_model == c.j_model {
+                        let omega_ij = angular_velocities[c.i] - angular_velocities[c.j];
+                        *acceleration += math::angular_cross(&omega_ij, &c.gradient)
+                            * (vortex_viscosity * volumes[c.j] * density0 / densities[c.i]);
+                    }
+                }
+            })
+    }
+
+    fn apply_permutation(&mut self, permutation: &[usize]) {
+        if self.angular_velocities.len() == permutation.len() {
+            self.angular_velocities =
+                crate::z_order::apply_permutation(permutation, &self.angular_velocities);
+        }
+    }
+
+    fn apply_particles_removal(&mut self, deleted_particles: &[bool]) {
+        // The particles added since the last step are at the end of the mask, and have no angular velocity yet.
+        let deleted_particles = &deleted_particles[..self.angular_velocities.len()];
+        crate::helper::filter_from_mask(deleted_particles, &mut self.angular_velocities);
+        self.vorticities.truncate(self.angular_velocities.len());
+    }
+}
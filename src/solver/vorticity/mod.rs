@@ -0,0 +1,5 @@
+pub use self::bender2017_micropolar::Bender2017Micropolar;
+pub use self::vorticity_confinement::VorticityConfinement;
+
+mod bender2017_micropolar;
+mod vorticity_confinement;
@@ -0,0 +1,129 @@
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+use na::{self, RealField, Unit};
+
+use crate::counters::Counters;
+use crate::geometry::ParticlesContacts;
+use crate::math::{self, AngularVector, Vector};
+use crate::object::{Boundary, Fluid};
+use crate::solver::NonPressureForce;
+use crate::TimestepManager;
+
+/// Computes the vorticity `curl(v)` of each particle of the given fluid.
+pub(crate) fn compute_vorticities<N: RealField>(
+    fluid_fluid_contacts: &ParticlesContacts<N>,
+    fluid: &Fluid<N>,
+    densities: &[N],
+    vorticities: &mut [AngularVector<N>],
+) {
+    let velocities = &fluid.velocities;
+
+    par_iter_mut!(vorticities)
+        .enumerate()
+        .for_each(|(i, vorticity_i)| {
+            let mut vorticity = AngularVector::zeros();
+
+            for c in fluid_fluid_contacts
+                .particle_contacts(i)
+                .read()
+                .unwrap()
+                .iter()
+            {
+                if c.i_model == c.j_model {
+                    let v_ji = velocities[c.j] - velocities[c.i];
+                    vorticity += math::gcross_matrix(&v_ji)
+                        * c.gradient
+                        * (fluid.particle_mass(c.j) / densities[c.j]);
+                }
+            }
+
+            // The gradient is taken w.r.t. `x_i` so we have to negate the result.
+            *vorticity_i = -vorticity;
+        })
+}
+
+// http://physbam.stanford.edu/~fedkiw/papers/stanford2001-01.pdf
+/// Vorticity confinement introduced by Fedkiw et al. 2001.
+///
+/// This re-injects in the fluid the small-scale vortices that are dissipated
+/// by the SPH discretization.
+#[derive(Clone)]
+pub struct VorticityConfinement<N: RealField> {
+    /// The strength of the vorticity confinement.
+    pub epsilon: N,
+    vorticities: Vec<AngularVector<N>>,
+}
+
+impl<N: RealField> VorticityConfinement<N> {
+    /// Initializes a vorticity confinement with the given strength.
+    pub fn new(epsilon: N) -> Self {
+        Self {
+            epsilon,
+            vorticities: Vec::new(),
+        }
+    }
+
+    fn init(&mut self, fluid: &Fluid<N>) {
+        if self.vorticities.len() != fluid.num_particles() {
+            self.vorticities
+                .resize(fluid.num_particles(), AngularVector::zeros());
+        }
+    }
+}
+
+impl<N: RealField> NonPressureForce<N> for VorticityConfinement<N> {
+    fn solve(
+        &mut self,
+        _counters: &mut Counters,
+        _timestep: &TimestepManager<N>,
+        _kernel_radius: N,
+        fluid_fluid_contacts: &ParticlesContacts<N>,
+        _fluid_boundaries_contacts: &ParticlesContacts<N>,
+        fluid: &mut Fluid<N>,
+        _boundaries: &[Boundary<N>],
+        densities: &[N],
+    ) {
+        if self.epsilon.is_zero() {
+            return;
+        }
+
+        self.init(fluid);
+        compute_vorticities(
+            fluid_fluid_contacts,
+            fluid,
+            densities,
+            &mut self.vorticities,
+        );
+
+        let epsilon = self.epsilon;
+        let vorticities = &self.vorticities;
+        let volumes = &fluid.volumes;
+        let density0 = fluid.density0;
+
+        par_iter_mut!(fluid.accelerations)
+            .enumerate()
+            .for_each(|(i, acceleration)| {
+                // Gradient of the vorticity norm.
+                let mut eta = Vector::zeros();
+
+                for c in fluid_fluid_contacts
+                    .particle_contacts(i)
+                    .read()
+                    .unwrap()
+                    .iter()
+                {
+                    if c.i_model == c.j_model {
+                        eta += c.gradient
+                            * (vorticities[c.j].norm() * volumes[c.j] * density0 / densities[c.j]);
+                    }
+                }
+
+                if let Some(normal) = Unit::try_new(eta, N::default_epsilon()) {
+                    *acceleration -= math::angular_cross(&vorticities[i], &normal) * epsilon;
+                }
+            })
+    }
+
+    fn apply_permutation(&mut self, _: &[usize]) {}
+}
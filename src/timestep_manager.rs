@@ -55,6 +55,18 @@ impl<N: RealField> TimestepManager<N> {
         self.remaining_time <= N::default_epsilon()
     }
 
+    /// The time elapsed since the beginning of the current timestep.
+    #[inline]
+    pub fn elapsed_time(&self) -> N {
+        self.total_step_size - self.remaining_time
+    }
+
+    /// The time remaining until the end of the current timestep.
+    #[inline]
+    pub fn remaining_time(&self) -> N {
+        self.remaining_time
+    }
+
     /// The current substep length.
     #[inline]
     pub fn dt(&self) -> N {